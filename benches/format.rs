@@ -0,0 +1,73 @@
+//! Compares the cost of formatting an event with [`Glog`] against `tracing-subscriber`'s
+//! [`Full`] formatter. That formatting such events does not allocate is checked by
+//! `tests/allocations.rs`.
+//!
+//! [`Full`]: tracing_subscriber::fmt::format::Full
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::io;
+use tracing::{info, info_span, Dispatch};
+use tracing_glog::{CachedUtcTime, Glog, GlogFields};
+
+fn glog() -> Dispatch {
+    Dispatch::new(
+        tracing_subscriber::fmt()
+            .with_writer(io::sink)
+            .event_format(Glog::default())
+            .fmt_fields(GlogFields::default())
+            .finish(),
+    )
+}
+
+fn glog_cached_time() -> Dispatch {
+    Dispatch::new(
+        tracing_subscriber::fmt()
+            .with_writer(io::sink)
+            .event_format(Glog::default().with_timer(CachedUtcTime::new()))
+            .fmt_fields(GlogFields::default())
+            .finish(),
+    )
+}
+
+fn full() -> Dispatch {
+    Dispatch::new(tracing_subscriber::fmt().with_writer(io::sink).finish())
+}
+
+fn event() {
+    info!(yaks = 3, shaved = true, ratio = 0.5, "shaving yaks");
+}
+
+fn event_in_span() {
+    let span = info_span!("shaving_yaks", yaks = 3);
+    let _enter = span.enter();
+    info!(yak = 1, shaved = true, "shaved yak");
+}
+
+fn format_event(c: &mut Criterion) {
+    let formatters = [
+        ("Glog", glog()),
+        ("Glog+CachedUtcTime", glog_cached_time()),
+        ("Full", full()),
+    ];
+
+    let mut group = c.benchmark_group("format_event");
+    group.throughput(Throughput::Elements(1));
+    for (name, dispatch) in &formatters {
+        tracing::dispatcher::with_default(dispatch, || {
+            group.bench_function(*name, |b| b.iter(event));
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("format_event_in_span");
+    group.throughput(Throughput::Elements(1));
+    for (name, dispatch) in &formatters {
+        tracing::dispatcher::with_default(dispatch, || {
+            group.bench_function(*name, |b| b.iter(event_in_span));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, format_event);
+criterion_main!(benches);
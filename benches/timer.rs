@@ -0,0 +1,36 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use tracing_glog::{CachedUtcTime, UtcTime};
+use tracing_subscriber::fmt::{format::Writer, time::FormatTime};
+
+fn format_time(c: &mut Criterion) {
+    let mut group = c.benchmark_group("format_time");
+    group.throughput(Throughput::Elements(1));
+
+    let mut buf = String::with_capacity(64);
+    let timer = UtcTime::default();
+    group.bench_function("UtcTime", |b| {
+        b.iter(|| {
+            buf.clear();
+            black_box(&timer)
+                .format_time(&mut Writer::new(&mut buf))
+                .unwrap();
+            black_box(&buf);
+        })
+    });
+
+    let timer = CachedUtcTime::new();
+    group.bench_function("CachedUtcTime", |b| {
+        b.iter(|| {
+            buf.clear();
+            black_box(&timer)
+                .format_time(&mut Writer::new(&mut buf))
+                .unwrap();
+            black_box(&buf);
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, format_time);
+criterion_main!(benches);
@@ -0,0 +1,53 @@
+use structopt::StructOpt;
+use tracing::{debug, info, span, trace, warn, Level};
+use tracing_glog::GlogArgs;
+
+/// To log to stderr, run:
+/// ```bash
+/// cargo run --example glog-flags --features structopt -- --logtostderr
+/// ```
+///
+/// Lines are colored when stderr is a terminal. Set `NO_COLOR=1` to disable colors, or
+/// `CLICOLOR_FORCE=1` to keep them when piping the output.
+///
+/// To write glog-style log files to the temporary directory instead, run:
+/// ```bash
+/// cargo run --example glog-flags --features structopt
+/// ```
+///
+/// All of glog's flags are supported, spelled as in glog; for instance, `--v=2` also shows
+/// `TRACE` events, and `--alsologtostderr=true` copies the log files to stderr.
+
+#[derive(Debug, StructOpt)]
+struct Args {
+    #[structopt(flatten)]
+    glog: GlogArgs,
+
+    /// The number of yaks to shave.
+    #[structopt(long, default_value = "3")]
+    yaks: usize,
+}
+
+fn main() {
+    let args = Args::from_args();
+
+    let options = args
+        .glog
+        .options()
+        .expect("Unable to parse glog flags")
+        .with_colorlogtostderr(true);
+    let _guard = options.init().expect("Unable to set up logging");
+
+    info!(yaks = args.yaks, "preparing to shave yaks");
+    let span = span!(Level::INFO, "shaving_yaks", yaks = args.yaks);
+    let _enter = span.enter();
+    for yak in 1..=args.yaks {
+        trace!(yak, "hello! I'm gonna shave a yak");
+        if yak == args.yaks {
+            warn!(yak, "could not locate yak");
+        } else {
+            debug!(yak, "yak shaved successfully");
+        }
+    }
+    info!("yak shaving completed");
+}
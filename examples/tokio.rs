@@ -1,7 +1,7 @@
 use anyhow::Error;
 use tokio::task::JoinSet;
 use tracing::{debug, info, instrument, span, Instrument as _, Level};
-use tracing_glog::{Glog, GlogFields, UtcTime};
+use tracing_glog::{Glog, GlogFields, NonBlocking, ShutdownGuard, UtcTime};
 
 #[instrument]
 async fn parent_task(subtasks: usize) -> Result<(), Error> {
@@ -28,9 +28,28 @@ async fn subtask(number: usize) -> usize {
     number
 }
 
+/// Resolves when the process is asked to terminate.
+async fn terminate() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut sigterm = signal(SignalKind::terminate())?;
+        tokio::select! {
+            _ = sigterm.recv() => Ok(()),
+            result = tokio::signal::ctrl_c() => result,
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Error> {
+    // Drains the background writer when `main` returns, including after a signal.
+    let _shutdown = ShutdownGuard::new();
+    let (writer, _guard) = NonBlocking::new(std::io::stderr());
     tracing_subscriber::fmt()
+        .with_writer(writer)
         .with_ansi(true)
         .event_format(
             Glog::default()
@@ -40,6 +59,13 @@ async fn main() -> Result<(), Error> {
         )
         .fmt_fields(GlogFields::default())
         .init();
-    parent_task(10).await?;
+
+    tokio::select! {
+        result = parent_task(10) => result?,
+        result = terminate() => {
+            result?;
+            info!("received a termination signal, shutting down");
+        }
+    }
     Ok(())
 }
@@ -1,24 +1,18 @@
 use structopt::StructOpt;
 use thiserror::Error;
 use tracing::{debug, error, info, span, trace, warn, Level};
-use tracing_glog::{Glog, GlogFields};
+use tracing_glog::{AnsiMode, Glog, GlogFields};
 
-/// To run with ANSI colors, run:
-/// ```bash
-/// cargo run --example yak-shave -- --with-ansi
-/// ```
-///
-/// To run without ANSI colors, run:
+/// To run, use:
 /// ```bash
 /// cargo run --example yak-shave
 /// ```
+///
+/// Lines are colored when stdout is a terminal. Set `NO_COLOR=1` to disable colors, or
+/// `CLICOLOR_FORCE=1` to keep them when piping the output.
 
 #[derive(Debug, structopt::StructOpt)]
 struct Args {
-    /// Whether to run this example with or without ANSI colors.
-    #[structopt(long)]
-    with_ansi: bool,
-
     /// Whether tracing-glog should include the span context.
     #[structopt(long)]
     with_span_context: bool,
@@ -27,10 +21,15 @@ struct Args {
 fn main() {
     let args = Args::from_args();
 
+    // `fmt()` writes to stdout.
+    let stdout = std::io::stdout();
     tracing_subscriber::fmt()
-        .with_ansi(args.with_ansi)
-        .event_format(Glog::default().with_span_context(args.with_span_context))
-        .fmt_fields(GlogFields::default())
+        .event_format(
+            Glog::default()
+                .with_span_context(args.with_span_context)
+                .with_ansi_mode(AnsiMode::Auto, &stdout),
+        )
+        .fmt_fields(GlogFields::default().with_ansi_mode(AnsiMode::Auto, &stdout))
         .init();
 
     let number_of_yaks = 3;
@@ -0,0 +1,140 @@
+use std::{env, io::IsTerminal};
+
+/// Whether lines are styled with ANSI escapes.
+///
+/// By default, [`Glog`] and [`GlogFields`] style lines if the writer has ANSI escapes enabled,
+/// as set by [`fmt::Layer::with_ansi`]. An `AnsiMode` passed to [`Glog::with_ansi_mode`] and
+/// [`GlogFields::with_ansi_mode`] overrides that setting.
+///
+/// [`Glog`]: crate::Glog
+/// [`GlogFields`]: crate::GlogFields
+/// [`Glog::with_ansi_mode`]: crate::Glog::with_ansi_mode
+/// [`GlogFields::with_ansi_mode`]: crate::GlogFields::with_ansi_mode
+/// [`fmt::Layer::with_ansi`]: tracing_subscriber::fmt::Layer::with_ansi
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AnsiMode {
+    /// Style lines if the stream they are written to is a terminal, following the common
+    /// conventions for overriding that choice, in this order:
+    ///
+    /// - a non-empty [`NO_COLOR`] disables styling;
+    /// - a [`CLICOLOR_FORCE`] other than `0` enables styling, even if the stream is not a
+    ///   terminal;
+    /// - `CLICOLOR=0` disables styling;
+    /// - `TERM=dumb` disables styling, unless `CLICOLOR` is set to another value.
+    ///
+    /// [`Glog`] and [`GlogFields`] do not know where their lines are written, so their
+    /// `with_ansi_mode` methods take the stream to check.
+    ///
+    /// [`NO_COLOR`]: https://no-color.org
+    /// [`CLICOLOR_FORCE`]: https://bixense.com/clicolors/
+    /// [`Glog`]: crate::Glog
+    /// [`GlogFields`]: crate::GlogFields
+    #[default]
+    Auto,
+    /// Always style lines.
+    Always,
+    /// Never style lines.
+    Never,
+}
+
+impl AnsiMode {
+    /// Returns whether lines written to `stream` are styled in this mode.
+    ///
+    /// ```
+    /// use tracing_glog::{AnsiMode, Glog};
+    ///
+    /// let ansi = AnsiMode::Auto.is_enabled_for(&std::io::stdout());
+    /// tracing_subscriber::fmt()
+    ///     .with_ansi(ansi)
+    ///     .event_format(Glog::default())
+    ///     .init();
+    /// ```
+    pub fn is_enabled_for(self, stream: &impl IsTerminal) -> bool {
+        match self {
+            AnsiMode::Always => true,
+            AnsiMode::Never => false,
+            AnsiMode::Auto => auto(stream.is_terminal(), |name| env::var(name).ok()),
+        }
+    }
+}
+
+/// Resolves [`AnsiMode::Auto`] for a stream, given a lookup of environment variables.
+fn auto(is_terminal: bool, env: impl Fn(&str) -> Option<String>) -> bool {
+    let var = |name| env(name).filter(|value| !value.is_empty());
+    if var("NO_COLOR").is_some() {
+        return false;
+    }
+    if var("CLICOLOR_FORCE").is_some_and(|force| force != "0") {
+        return true;
+    }
+    let clicolor = var("CLICOLOR").map(|clicolor| clicolor != "0");
+    if clicolor == Some(false) {
+        return false;
+    }
+    let dumb = var("TERM").is_some_and(|term| term == "dumb");
+    is_terminal && (clicolor == Some(true) || !dumb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auto_with(is_terminal: bool, vars: &[(&str, &str)]) -> bool {
+        auto(is_terminal, |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn terminals_are_styled() {
+        assert!(auto_with(true, &[]));
+        assert!(!auto_with(false, &[]));
+        assert!(auto_with(true, &[("TERM", "xterm-256color")]));
+        assert!(!auto_with(true, &[("TERM", "dumb")]));
+        assert!(auto_with(true, &[("TERM", "dumb"), ("CLICOLOR", "1")]));
+        assert!(!auto_with(true, &[("CLICOLOR", "0")]));
+        assert!(!auto_with(false, &[("CLICOLOR", "1")]));
+    }
+
+    #[test]
+    fn no_color_takes_precedence() {
+        assert!(!auto_with(true, &[("NO_COLOR", "1")]));
+        assert!(!auto_with(true, &[("NO_COLOR", "0")]));
+        assert!(!auto_with(
+            true,
+            &[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")]
+        ));
+        // An empty `NO_COLOR` is ignored.
+        assert!(auto_with(true, &[("NO_COLOR", "")]));
+        assert!(auto_with(
+            false,
+            &[("NO_COLOR", ""), ("CLICOLOR_FORCE", "1")]
+        ));
+    }
+
+    #[test]
+    fn clicolor_force_styles_any_stream() {
+        assert!(auto_with(false, &[("CLICOLOR_FORCE", "1")]));
+        assert!(auto_with(false, &[("CLICOLOR_FORCE", "yes")]));
+        assert!(auto_with(
+            true,
+            &[("CLICOLOR_FORCE", "1"), ("CLICOLOR", "0")]
+        ));
+        assert!(auto_with(
+            false,
+            &[("CLICOLOR_FORCE", "1"), ("TERM", "dumb")]
+        ));
+        assert!(!auto_with(false, &[("CLICOLOR_FORCE", "0")]));
+        assert!(auto_with(true, &[("CLICOLOR_FORCE", "0")]));
+        assert!(!auto_with(false, &[("CLICOLOR_FORCE", "")]));
+    }
+
+    #[test]
+    fn explicit_modes_ignore_the_stream() {
+        let stream = std::io::stderr();
+        assert!(AnsiMode::Always.is_enabled_for(&stream));
+        assert!(!AnsiMode::Never.is_enabled_for(&stream));
+    }
+}
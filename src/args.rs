@@ -0,0 +1,246 @@
+use crate::{
+    config::{parse_bool, parse_severity, parse_vmodule, BOOL, SEVERITY, VMODULE},
+    ConfigError, GlogOptions, ShutdownGuard,
+};
+use std::path::PathBuf;
+use structopt::StructOpt;
+use tracing::level_filters::LevelFilter;
+
+/// glog's command-line flags, to be flattened into a program's own [`StructOpt`] arguments.
+///
+/// The flags are spelled as in glog, such as `--v=2` and `--log_dir=/var/log/yaks`, and are
+/// described in [`GlogOptions`]. As in glog, boolean flags can be given on their own, as in
+/// `--logtostderr`, or with a value, as in `--logtostderr=1` or `--log_prefix=false`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use structopt::StructOpt;
+/// use tracing_glog::GlogArgs;
+///
+/// #[derive(Debug, StructOpt)]
+/// struct Args {
+///     #[structopt(flatten)]
+///     glog: GlogArgs,
+///
+///     /// The number of yaks to shave.
+///     #[structopt(long, default_value = "3")]
+///     yaks: usize,
+/// }
+///
+/// let args = Args::from_args();
+/// let _guard = args.glog.init().expect("Unable to set up logging");
+/// tracing::info!(yaks = args.yaks, "preparing to shave yaks");
+/// ```
+///
+/// This type is available with the `structopt` feature.
+#[derive(Clone, Debug, StructOpt)]
+pub struct GlogArgs {
+    /// Show DEBUG events at verbosity 1 and TRACE events at verbosity 2 or more.
+    #[structopt(long = "v", default_value = "0", allow_hyphen_values = true)]
+    v: i32,
+
+    /// Per-module verbosity, as a comma-separated list of `<pattern>=<verbosity>`.
+    #[structopt(long = "vmodule", validator = validate_vmodule)]
+    vmodule: Option<String>,
+
+    /// Log to stderr instead of to log files.
+    #[structopt(
+        long = "logtostderr",
+        require_equals = true,
+        validator = validate_bool
+    )]
+    logtostderr: Option<Option<String>>,
+
+    /// Log to stderr as well as to log files.
+    #[structopt(
+        long = "alsologtostderr",
+        require_equals = true,
+        validator = validate_bool
+    )]
+    alsologtostderr: Option<Option<String>>,
+
+    /// Color the lines logged to stderr.
+    #[structopt(
+        long = "colorlogtostderr",
+        require_equals = true,
+        validator = validate_bool
+    )]
+    colorlogtostderr: Option<Option<String>>,
+
+    /// Copy lines at or above this severity to stderr when logging to files.
+    #[structopt(
+        long = "stderrthreshold",
+        default_value = "ERROR",
+        parse(try_from_str = parse_severity_arg)
+    )]
+    stderrthreshold: LevelFilter,
+
+    /// Discard lines below this severity.
+    #[structopt(
+        long = "minloglevel",
+        default_value = "INFO",
+        parse(try_from_str = parse_severity_arg)
+    )]
+    minloglevel: LevelFilter,
+
+    /// Write log files to this directory instead of the temporary directory.
+    #[structopt(long = "log_dir", parse(from_os_str))]
+    log_dir: Option<PathBuf>,
+
+    /// Write the glog prefix at the start of each line.
+    #[structopt(
+        long = "log_prefix",
+        require_equals = true,
+        validator = validate_bool
+    )]
+    log_prefix: Option<Option<String>>,
+
+    /// Start a new log file after this many megabytes.
+    #[structopt(long = "max_log_size", default_value = "1800")]
+    max_log_size: u64,
+}
+
+impl GlogArgs {
+    /// Returns the [`GlogOptions`] set by these flags.
+    pub fn options(&self) -> Result<GlogOptions, ConfigError> {
+        let mut options = GlogOptions::default()
+            .with_verbosity(self.v)
+            .with_logtostderr(flag("logtostderr", &self.logtostderr, false)?)
+            .with_alsologtostderr(flag("alsologtostderr", &self.alsologtostderr, false)?)
+            .with_colorlogtostderr(flag("colorlogtostderr", &self.colorlogtostderr, false)?)
+            .with_stderrthreshold(self.stderrthreshold)
+            .with_minloglevel(self.minloglevel)
+            .with_log_prefix(flag("log_prefix", &self.log_prefix, true)?)
+            .with_max_log_size(self.max_log_size);
+        if let Some(vmodule) = &self.vmodule {
+            options = options.with_vmodule(vmodule)?;
+        }
+        if let Some(log_dir) = &self.log_dir {
+            options = options.with_log_dir(log_dir);
+        }
+        Ok(options)
+    }
+
+    /// Builds the subscriber described by these flags and sets it as the global default.
+    ///
+    /// See [`GlogOptions::init`].
+    pub fn init(&self) -> Result<ShutdownGuard, ConfigError> {
+        self.options()?.init()
+    }
+}
+
+fn validate_vmodule(value: String) -> Result<(), String> {
+    match parse_vmodule(&value) {
+        Some(_) => Ok(()),
+        None => Err(format!("expected {}", VMODULE)),
+    }
+}
+
+fn parse_severity_arg(value: &str) -> Result<LevelFilter, String> {
+    parse_severity(value).ok_or_else(|| format!("expected {}", SEVERITY))
+}
+
+/// Returns the value of a boolean flag, which is `true` when it is given without a value.
+fn flag(name: &str, value: &Option<Option<String>>, default: bool) -> Result<bool, ConfigError> {
+    match value {
+        None => Ok(default),
+        Some(None) => Ok(true),
+        Some(Some(value)) => parse_bool(value).ok_or_else(|| ConfigError::InvalidValue {
+            key: name.to_string(),
+            value: value.clone(),
+            expected: BOOL,
+        }),
+    }
+}
+
+fn validate_bool(value: String) -> Result<(), String> {
+    match parse_bool(&value) {
+        Some(_) => Ok(()),
+        None => Err(format!("expected {}", BOOL)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<GlogArgs, String> {
+        let args = std::iter::once("yak-shave").chain(args.iter().copied());
+        GlogArgs::from_iter_safe(args).map_err(|e| e.message)
+    }
+
+    /// Returns `logtostderr`, `alsologtostderr`, `colorlogtostderr` and `log_prefix`.
+    fn flags(args: &[&str]) -> [bool; 4] {
+        let args = parse(args).unwrap();
+        [
+            flag("logtostderr", &args.logtostderr, false).unwrap(),
+            flag("alsologtostderr", &args.alsologtostderr, false).unwrap(),
+            flag("colorlogtostderr", &args.colorlogtostderr, false).unwrap(),
+            flag("log_prefix", &args.log_prefix, true).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn boolean_flags_take_an_optional_value() {
+        assert_eq!(flags(&[]), [false, false, false, true]);
+        assert_eq!(
+            flags(&["--logtostderr", "--colorlogtostderr", "--log_prefix"]),
+            [true, false, true, true]
+        );
+        assert_eq!(
+            flags(&[
+                "--logtostderr=1",
+                "--alsologtostderr=true",
+                "--colorlogtostderr=yes",
+                "--log_prefix=false",
+            ]),
+            [true, true, true, false]
+        );
+        assert_eq!(
+            flags(&["--logtostderr=0", "--alsologtostderr=f", "--log_prefix=n"]),
+            [false, false, false, false]
+        );
+    }
+
+    #[test]
+    fn boolean_flags_do_not_consume_the_next_argument() {
+        assert!(parse(&["--logtostderr", "true"]).is_err());
+        let args = parse(&["--logtostderr", "--v=2"]).unwrap();
+        assert_eq!(args.logtostderr, Some(None));
+        assert_eq!(args.v, 2);
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        for args in [
+            "--logtostderr=maybe",
+            "--log_prefix=2",
+            "--minloglevel=4",
+            "--vmodule=shaving",
+            "--v=loud",
+        ] {
+            assert!(parse(&[args]).is_err(), "{}", args);
+        }
+    }
+
+    #[test]
+    fn flags_are_spelled_as_in_glog() {
+        let args = parse(&[
+            "--v=-1",
+            "--vmodule=shaving=2",
+            "--stderrthreshold=WARNING",
+            "--minloglevel=1",
+            "--log_dir=/var/log/yaks",
+            "--max_log_size=10",
+        ])
+        .unwrap();
+        assert_eq!(args.v, -1);
+        assert_eq!(args.vmodule.as_deref(), Some("shaving=2"));
+        assert_eq!(args.stderrthreshold, LevelFilter::WARN);
+        assert_eq!(args.minloglevel, LevelFilter::WARN);
+        assert_eq!(args.log_dir, Some(PathBuf::from("/var/log/yaks")));
+        assert_eq!(args.max_log_size, 10);
+        assert!(args.options().is_ok());
+    }
+}
@@ -0,0 +1,203 @@
+use crate::nu_ansi_term::Color;
+use std::collections::HashMap;
+
+/// Colors for names, such as targets or span names, that stay the same from one line, run
+/// and process to the next, so that subsystems can be told apart at a glance.
+///
+/// A name is given the color it was assigned with [`NameColors::with_color`], or else a color
+/// picked by hashing the name. Hashed colors are drawn from the 256-color palette or, for
+/// terminals that support it, from the full range of 24-bit colors; see [`ColorDepth`].
+///
+/// Use [`Glog::with_target_colors`] to color targets and [`Glog::with_span_colors`] to color
+/// the name of the outermost span in the span context.
+///
+/// This type is available with the `ansi` feature.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_glog::{Color, ColorDepth, Glog, GlogFields, NameColors};
+///
+/// let colors = NameColors::new(ColorDepth::TrueColor).with_color("yak_shave", Color::Green);
+/// tracing_subscriber::fmt()
+///     .event_format(
+///         Glog::default()
+///             .with_target(true)
+///             .with_target_colors(colors.clone())
+///             .with_span_colors(colors),
+///     )
+///     .fmt_fields(GlogFields::default())
+///     .init();
+/// ```
+///
+/// [`Glog::with_target_colors`]: crate::Glog::with_target_colors
+/// [`Glog::with_span_colors`]: crate::Glog::with_span_colors
+#[derive(Clone, Debug)]
+pub struct NameColors {
+    depth: ColorDepth,
+    colors: HashMap<String, Color>,
+}
+
+/// The colors a terminal can display, from which [`NameColors`] picks hashed colors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// The 256-color palette supported by most terminals.
+    #[default]
+    Ansi256,
+    /// 24-bit colors, for terminals that set `COLORTERM=truecolor`.
+    TrueColor,
+}
+
+impl NameColors {
+    /// Returns colors that are hashed from names, using colors of the given depth.
+    pub fn new(depth: ColorDepth) -> Self {
+        NameColors {
+            depth,
+            colors: HashMap::new(),
+        }
+    }
+
+    /// Colors `name` with `color` instead of a hashed color.
+    pub fn with_color(mut self, name: impl Into<String>, color: Color) -> Self {
+        self.colors.insert(name.into(), color);
+        self
+    }
+
+    /// Returns the color of `name`.
+    pub(crate) fn color_for(&self, name: &str) -> Color {
+        if let Some(color) = self.colors.get(name) {
+            return *color;
+        }
+        let hash = fnv1a(name);
+        match self.depth {
+            ColorDepth::Ansi256 => {
+                // Pick from the 6x6x6 color cube, leaving out its darkest shades and grays,
+                // which are hard to tell from the rest of the line.
+                let n = (hash % 125) as u8;
+                let (r, g, mut b) = (1 + n / 25, 1 + n / 5 % 5, 1 + n % 5);
+                if r == g && g == b {
+                    b = if b == 1 { 3 } else { 1 };
+                }
+                Color::Fixed(16 + 36 * r + 6 * g + b)
+            }
+            ColorDepth::TrueColor => {
+                let (r, g, b) = hsl_to_rgb((hash % 360) as f32, 0.65, 0.6);
+                Color::Rgb(r, g, b)
+            }
+        }
+    }
+}
+
+/// The 64-bit FNV-1a hash, which is stable across platforms and Rust versions, unlike the
+/// hashers in `std`.
+fn fnv1a(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (u8, u8, u8) {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let h = hue / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |c: f32| ((c + m) * 255.0).round() as u8;
+    (channel(r), channel(g), channel(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::format_with, Glog, GlogFields};
+
+    #[test]
+    fn hashed_colors_are_stable() {
+        // The published FNV-1a test vectors.
+        assert_eq!(fnv1a(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a("a"), 0xaf63_dc4c_8601_ec8c);
+
+        let ansi256 = NameColors::new(ColorDepth::Ansi256);
+        assert_eq!(ansi256.color_for("yak_shave"), Color::Fixed(150));
+        assert_eq!(ansi256.color_for("tokio::runtime"), Color::Fixed(116));
+        assert_eq!(ansi256.color_for(""), Color::Fixed(109));
+
+        let true_color = NameColors::new(ColorDepth::TrueColor);
+        assert_eq!(true_color.color_for("yak_shave"), Color::Rgb(87, 219, 210));
+        assert_eq!(
+            true_color.color_for("tokio::runtime"),
+            Color::Rgb(219, 87, 213)
+        );
+        assert_eq!(true_color.color_for(""), Color::Rgb(182, 219, 87));
+    }
+
+    #[test]
+    fn explicit_colors_win() {
+        for depth in [ColorDepth::Ansi256, ColorDepth::TrueColor] {
+            let colors = NameColors::new(depth).with_color("yak_shave", Color::Green);
+            assert_eq!(colors.color_for("yak_shave"), Color::Green);
+            assert_eq!(
+                colors.color_for("tokio::runtime"),
+                NameColors::new(depth).color_for("tokio::runtime")
+            );
+        }
+    }
+
+    #[test]
+    fn ansi256_colors_are_neither_dark_nor_gray() {
+        let colors = NameColors::new(ColorDepth::Ansi256);
+        for i in 0..10_000 {
+            let name = format!("target_{}", i);
+            let Color::Fixed(index) = colors.color_for(&name) else {
+                panic!("{} is not in the 256-color palette", name);
+            };
+            // Indices 16 to 231 are the 6x6x6 color cube.
+            assert!((16..232).contains(&index), "{}: {}", name, index);
+            let cube = index - 16;
+            let (r, g, b) = (cube / 36, cube / 6 % 6, cube % 6);
+            assert!(r > 0 && g > 0 && b > 0, "{}: {}", name, index);
+            assert!(!(r == g && g == b), "{}: {} is gray", name, index);
+        }
+    }
+
+    #[test]
+    fn hsl_conversion() {
+        assert_eq!(hsl_to_rgb(0.0, 1.0, 0.5), (255, 0, 0));
+        assert_eq!(hsl_to_rgb(120.0, 1.0, 0.5), (0, 255, 0));
+        assert_eq!(hsl_to_rgb(240.0, 1.0, 0.5), (0, 0, 255));
+        assert_eq!(hsl_to_rgb(0.0, 0.0, 1.0), (255, 255, 255));
+    }
+
+    #[test]
+    fn targets_and_root_spans_are_colored() {
+        let colors = NameColors::new(ColorDepth::Ansi256).with_color("yak", Color::Green);
+        let glog = Glog::default()
+            .with_target(true)
+            .with_target_colors(colors.clone())
+            .with_span_colors(colors);
+        let output = format_with(glog, GlogFields::default(), true, || {
+            let _root = tracing::info_span!("yak").entered();
+            let _inner = tracing::info_span!("inner").entered();
+            tracing::info!(target: "yak", "hello");
+        });
+        // The target is colored within the bold callsite, which is restored after it.
+        assert!(
+            output.contains("\x1b[1m [\x1b[1;32myak\x1b[0m\x1b[1m] src/colors.rs:"),
+            "{:?}",
+            output
+        );
+        // Only the outermost span is colored.
+        assert!(
+            output.contains("] [\x1b[1;32myak\x1b[0m, \x1b[1minner\x1b[0m] hello\n"),
+            "{:?}",
+            output
+        );
+    }
+}
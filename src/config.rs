@@ -0,0 +1,993 @@
+use crate::{Glog, GlogWriter, LogFiles, ShutdownGuard};
+use arc_swap::ArcSwap;
+use std::{
+    error,
+    ffi::OsString,
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tracing::{level_filters::LevelFilter, Level, Metadata, Subscriber};
+use tracing_subscriber::{
+    layer::{Context, Layer, SubscriberExt},
+    Registry,
+};
+
+/// The glog flags that `tracing-glog` understands, in the order they are read.
+const FLAGS: [&str; 10] = [
+    "v",
+    "vmodule",
+    "logtostderr",
+    "alsologtostderr",
+    "colorlogtostderr",
+    "stderrthreshold",
+    "minloglevel",
+    "log_dir",
+    "log_prefix",
+    "max_log_size",
+];
+
+/// The logging setup described by glog's flags.
+///
+/// `GlogOptions` maps glog's flags onto `tracing`: `INFO`, `WARNING` and `ERROR` are the
+/// `INFO`, `WARN` and `ERROR` levels, and glog's verbose logs are `DEBUG` (verbosity 1) and
+/// `TRACE` (verbosity 2) events. `FATAL` has no `tracing` equivalent, so a threshold of
+/// `FATAL` disables the corresponding output.
+///
+/// | Flag                 | Default  | Effect                                                  |
+/// |----------------------|----------|---------------------------------------------------------|
+/// | `v`                  | 0        | Enables `DEBUG` events at 1 and `TRACE` events at 2      |
+/// | `vmodule`            |          | Per-module verbosity, such as `shaving=2,yak*=1`         |
+/// | `logtostderr`        | false    | Logs to stderr only, rather than to files                |
+/// | `alsologtostderr`    | false    | Logs to stderr in addition to files                      |
+/// | `colorlogtostderr`   | false    | Colors the lines written to stderr                       |
+/// | `stderrthreshold`    | `ERROR`  | Copies lines of this severity and above to stderr        |
+/// | `minloglevel`        | `INFO`   | Discards lines below this severity                       |
+/// | `log_dir`            | temp dir | The directory log files are written to                   |
+/// | `log_prefix`         | true     | Writes the glog prefix on each line                      |
+/// | `max_log_size`       | 1800     | The size in megabytes after which a new file is started  |
+///
+/// Severities can be given by name or number (`INFO` = 0 up to `FATAL` = 3), and booleans as
+/// `true`/`false`, `1`/`0`, `yes`/`no` or `t`/`f`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use tracing_glog::GlogOptions;
+///
+/// // Reads GLOG_v, GLOG_logtostderr, and so on.
+/// let _guard = GlogOptions::from_env()?.init()?;
+/// tracing::info!("logging is set up");
+/// # Ok::<(), tracing_glog::ConfigError>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct GlogOptions {
+    v: i32,
+    vmodule: Vec<(String, i32)>,
+    logtostderr: bool,
+    alsologtostderr: bool,
+    colorlogtostderr: bool,
+    stderrthreshold: LevelFilter,
+    minloglevel: LevelFilter,
+    log_dir: Option<PathBuf>,
+    log_prefix: bool,
+    max_log_size: u64,
+    span_context: bool,
+}
+
+/// Reads glog's `GLOG_*` environment variables and installs the matching global subscriber.
+///
+/// This is a shorthand for [`GlogOptions::from_env`] followed by [`GlogOptions::init`]. The
+/// returned guard flushes the log files when it is dropped, so hold it until the end of
+/// `main`:
+///
+/// ```no_run
+/// fn main() -> Result<(), tracing_glog::ConfigError> {
+///     let _guard = tracing_glog::init_from_env()?;
+///     tracing::info!("logging is set up");
+///     Ok(())
+/// }
+/// ```
+pub fn init_from_env() -> Result<ShutdownGuard, ConfigError> {
+    GlogOptions::from_env()?.init()
+}
+
+impl Default for GlogOptions {
+    fn default() -> Self {
+        GlogOptions {
+            v: 0,
+            vmodule: Vec::new(),
+            logtostderr: false,
+            alsologtostderr: false,
+            colorlogtostderr: false,
+            stderrthreshold: LevelFilter::ERROR,
+            minloglevel: LevelFilter::TRACE,
+            log_dir: None,
+            log_prefix: true,
+            max_log_size: 1800,
+            span_context: true,
+        }
+    }
+}
+
+impl GlogOptions {
+    /// Reads the options from glog's `GLOG_<flag>` environment variables, such as `GLOG_v`
+    /// and `GLOG_log_dir`.
+    ///
+    /// Variables that are unset or empty keep their default. `GLOG_*` variables for flags that
+    /// `tracing-glog` does not support are ignored, so that the same environment can be
+    /// shared with C++ programs using glog.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_vars(|key| std::env::var_os(key))
+    }
+
+    /// Reads the options from the `GLOG_*` variables that `var` looks up.
+    fn from_vars(var: impl Fn(&str) -> Option<OsString>) -> Result<Self, ConfigError> {
+        let mut options = GlogOptions::default();
+        for flag in FLAGS {
+            let key = format!("GLOG_{}", flag);
+            let value = match var(&key) {
+                Some(value) if value.is_empty() => continue,
+                Some(value) => value,
+                None => continue,
+            };
+            let value = value.to_str().ok_or_else(|| ConfigError::InvalidValue {
+                key: key.clone(),
+                value: value.to_string_lossy().into_owned(),
+                expected: "valid UTF-8",
+            })?;
+            options.set(flag, value).map_err(|e| e.with_key(&key))?;
+        }
+        Ok(options)
+    }
+
+    /// Sets the glog flag `flag` from its textual value, as it would appear on the command
+    /// line or in a `GLOG_*` variable.
+    ///
+    /// Returns an error if `flag` is not one of the flags listed above, or if `value` is not
+    /// valid for it.
+    pub fn set(&mut self, flag: &str, value: &str) -> Result<(), ConfigError> {
+        let invalid = |expected| ConfigError::InvalidValue {
+            key: flag.to_string(),
+            value: value.to_string(),
+            expected,
+        };
+        match flag {
+            "v" => self.v = value.parse().map_err(|_| invalid("an integer"))?,
+            "vmodule" => self.vmodule = parse_vmodule(value).ok_or_else(|| invalid(VMODULE))?,
+            "logtostderr" => self.logtostderr = parse_bool(value).ok_or_else(|| invalid(BOOL))?,
+            "alsologtostderr" => {
+                self.alsologtostderr = parse_bool(value).ok_or_else(|| invalid(BOOL))?
+            }
+            "colorlogtostderr" => {
+                self.colorlogtostderr = parse_bool(value).ok_or_else(|| invalid(BOOL))?
+            }
+            "stderrthreshold" => {
+                self.stderrthreshold = parse_severity(value).ok_or_else(|| invalid(SEVERITY))?
+            }
+            "minloglevel" => {
+                self.minloglevel = parse_severity(value).ok_or_else(|| invalid(SEVERITY))?
+            }
+            "log_dir" => self.log_dir = Some(PathBuf::from(value)),
+            "log_prefix" => self.log_prefix = parse_bool(value).ok_or_else(|| invalid(BOOL))?,
+            "max_log_size" => {
+                self.max_log_size = value
+                    .parse()
+                    .map_err(|_| invalid("a non-negative integer"))?
+            }
+            _ => return Err(ConfigError::UnknownFlag(flag.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Sets the verbosity, like `--v`.
+    pub fn with_verbosity(self, v: i32) -> Self {
+        GlogOptions { v, ..self }
+    }
+
+    /// Sets per-module verbosities, like `--vmodule`, from a list such as `shaving=2,yak*=1`.
+    pub fn with_vmodule(self, vmodule: &str) -> Result<Self, ConfigError> {
+        let vmodule = parse_vmodule(vmodule).ok_or_else(|| ConfigError::InvalidValue {
+            key: "vmodule".to_string(),
+            value: vmodule.to_string(),
+            expected: VMODULE,
+        })?;
+        Ok(GlogOptions { vmodule, ..self })
+    }
+
+    /// Logs to stderr only, like `--logtostderr`.
+    pub fn with_logtostderr(self, logtostderr: bool) -> Self {
+        GlogOptions {
+            logtostderr,
+            ..self
+        }
+    }
+
+    /// Logs to stderr in addition to files, like `--alsologtostderr`.
+    pub fn with_alsologtostderr(self, alsologtostderr: bool) -> Self {
+        GlogOptions {
+            alsologtostderr,
+            ..self
+        }
+    }
+
+    /// Colors the lines written to stderr, like `--colorlogtostderr`.
+    pub fn with_colorlogtostderr(self, colorlogtostderr: bool) -> Self {
+        GlogOptions {
+            colorlogtostderr,
+            ..self
+        }
+    }
+
+    /// Sets the least severe level that is copied to stderr when logging to files, like
+    /// `--stderrthreshold`.
+    pub fn with_stderrthreshold(self, stderrthreshold: LevelFilter) -> Self {
+        GlogOptions {
+            stderrthreshold,
+            ..self
+        }
+    }
+
+    /// Sets the least severe level that is logged at all, like `--minloglevel`.
+    pub fn with_minloglevel(self, minloglevel: LevelFilter) -> Self {
+        GlogOptions {
+            minloglevel,
+            ..self
+        }
+    }
+
+    /// Sets the directory log files are written to, like `--log_dir`.
+    pub fn with_log_dir(self, log_dir: impl Into<PathBuf>) -> Self {
+        GlogOptions {
+            log_dir: Some(log_dir.into()),
+            ..self
+        }
+    }
+
+    /// Sets whether the glog prefix is written, like `--log_prefix`.
+    pub fn with_log_prefix(self, log_prefix: bool) -> Self {
+        GlogOptions { log_prefix, ..self }
+    }
+
+    /// Sets the size in megabytes after which a new log file is started, like
+    /// `--max_log_size`. Zero counts as one megabyte, as in glog, and sizes too large to count
+    /// in bytes never start a new file.
+    pub fn with_max_log_size(self, max_log_size: u64) -> Self {
+        GlogOptions {
+            max_log_size,
+            ..self
+        }
+    }
+
+    /// Sets whether the span context is written, as with [`Glog::with_span_context`].
+    /// Defaults to true.
+    pub fn with_span_context(self, span_context: bool) -> Self {
+        GlogOptions {
+            span_context,
+            ..self
+        }
+    }
+
+    /// Returns the filter implementing `v`, `vmodule` and `minloglevel`.
+    pub fn filter(&self) -> GlogFilter {
+        GlogFilter::new(self.v, self.vmodule.clone(), self.minloglevel)
+    }
+
+    fn event_format(&self) -> Glog {
+        Glog::default()
+            .with_prefix(self.log_prefix)
+            .with_span_context(self.span_context)
+    }
+
+    /// Returns the maximum size of a log file in bytes.
+    fn max_file_size(&self) -> u64 {
+        // glog never starts a new file for less than a megabyte. Sizes that do not fit in
+        // bytes are as good as unlimited.
+        self.max_log_size.max(1).saturating_mul(1 << 20)
+    }
+
+    /// Builds the subscriber described by these options and sets it as the global default.
+    ///
+    /// The returned guard calls [`shutdown`](crate::shutdown) when dropped, so that buffered
+    /// lines reach the log files.
+    pub fn init(self) -> Result<ShutdownGuard, ConfigError> {
+        let log_files = if self.logtostderr {
+            None
+        } else {
+            let log_dir = self.log_dir.clone().unwrap_or_else(std::env::temp_dir);
+            check_log_dir(&log_dir)?;
+            Some(LogFiles::new(log_dir).with_max_file_size(self.max_file_size()))
+        };
+        let writer = match log_files {
+            Some(log_files) => GlogWriter::new(log_files),
+            None => GlogWriter::stderr(),
+        };
+        let stderr_threshold = if self.alsologtostderr {
+            LevelFilter::TRACE
+        } else {
+            self.stderrthreshold
+        };
+        let layer = writer
+            .with_stderr_threshold(stderr_threshold)
+            .with_colorlogtostderr(self.colorlogtostderr)
+            .layer()
+            .event_format(self.event_format());
+
+        let subscriber = Registry::default().with(self.filter()).with(layer);
+        tracing::subscriber::set_global_default(subscriber)
+            .map_err(|_| ConfigError::AlreadyInitialized)?;
+        Ok(ShutdownGuard::new())
+    }
+}
+
+/// A filter implementing glog's `--v`, `--vmodule` and `--minloglevel` flags.
+///
+/// Events and spans at `INFO` and above are enabled if they are at least as severe as the
+/// minimum level. `DEBUG` and `TRACE` are glog's verbosity levels 1 and 2, and are enabled
+/// if the verbosity of their module is high enough. A module's verbosity is that of the first
+/// `vmodule` pattern matching it, or `v` otherwise. Patterns may contain `*` and `?`
+/// wildcards, and are matched against the basename of the source file without its extension
+/// (for instance, `shaving` for `src/yak/shaving.rs`) and against the last segment of the
+/// module path. A pattern containing `/` is matched against the source file path without its
+/// extension instead.
+///
+/// Since its decisions only depend on an event's metadata, `GlogFilter` is a global filter,
+/// and is usually the first layer of a subscriber. Use [`GlogOptions::filter`] to create one.
+///
+/// The verbosity and `vmodule` patterns can be changed while the filter is in use with a
+/// [`GlogFilterHandle`]. A clone of a `GlogFilter` starts out with the same settings, but
+/// is not affected by the handles of the original.
+#[derive(Debug)]
+pub struct GlogFilter {
+    verbosity: Arc<ArcSwap<Verbosity>>,
+    minloglevel: LevelFilter,
+}
+
+/// The settings of a [`GlogFilter`] that can be reloaded.
+#[derive(Debug)]
+struct Verbosity {
+    v: i32,
+    vmodule: Vec<(String, i32)>,
+}
+
+/// Changes the verbosity and `vmodule` patterns of a [`GlogFilter`] while it is in use.
+///
+/// A handle is returned by [`GlogFilter::reload_handle`], and stays connected to the filter
+/// after it has been installed in a subscriber. Handles are cheap to clone and can be sent
+/// to another thread, such as one serving an admin endpoint or waiting for `SIGHUP`.
+///
+/// A change replaces the settings atomically, so that no event is filtered with a mix of the
+/// old and new settings, and then asks `tracing` to re-evaluate every callsite. Filtering
+/// an event never takes a lock.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_subscriber::prelude::*;
+/// use tracing_glog::{Glog, GlogFields, GlogOptions};
+///
+/// let filter = GlogOptions::default().filter();
+/// let handle = filter.reload_handle();
+/// tracing_subscriber::registry()
+///     .with(filter)
+///     .with(
+///         tracing_subscriber::fmt::layer()
+///             .event_format(Glog::default())
+///             .fmt_fields(GlogFields::default()),
+///     )
+///     .init();
+///
+/// tracing::debug!("not logged");
+/// handle.set_verbosity(1);
+/// tracing::debug!("logged");
+/// handle.set_vmodule("yak_shave=0,shaving*=2").expect("invalid vmodule");
+/// ```
+///
+/// [`GlogFilter`]: crate::GlogFilter
+#[derive(Clone, Debug)]
+pub struct GlogFilterHandle {
+    verbosity: Arc<ArcSwap<Verbosity>>,
+}
+
+impl GlogFilter {
+    pub(crate) fn new(v: i32, vmodule: Vec<(String, i32)>, minloglevel: LevelFilter) -> Self {
+        GlogFilter {
+            verbosity: Arc::new(ArcSwap::from_pointee(Verbosity { v, vmodule })),
+            minloglevel,
+        }
+    }
+
+    /// Returns a handle that changes the verbosity and `vmodule` patterns of this filter
+    /// while it is in use.
+    ///
+    /// See [`GlogFilterHandle`].
+    pub fn reload_handle(&self) -> GlogFilterHandle {
+        GlogFilterHandle {
+            verbosity: self.verbosity.clone(),
+        }
+    }
+
+    fn is_enabled(&self, metadata: &Metadata<'_>) -> bool {
+        let level = *metadata.level();
+        if self.minloglevel < level {
+            return false;
+        }
+        let verbosity = match level {
+            Level::TRACE => 2,
+            Level::DEBUG => 1,
+            _ => return true,
+        };
+        verbosity <= self.verbosity.load().of(metadata)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.verbosity.load().max_level().min(self.minloglevel)
+    }
+}
+
+impl Clone for GlogFilter {
+    fn clone(&self) -> Self {
+        let verbosity = self.verbosity.load();
+        GlogFilter::new(verbosity.v, verbosity.vmodule.clone(), self.minloglevel)
+    }
+}
+
+impl Verbosity {
+    /// Returns the verbosity of the module that `metadata` belongs to.
+    fn of(&self, metadata: &Metadata<'_>) -> i32 {
+        let file = metadata.file().map(|file| {
+            let path = Path::new(file);
+            let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(file);
+            let without_extension = file.strip_suffix(".rs").unwrap_or(file);
+            (stem, without_extension)
+        });
+        let module = metadata
+            .module_path()
+            .unwrap_or_else(|| metadata.target())
+            .rsplit("::")
+            .next();
+
+        for (pattern, v) in &self.vmodule {
+            let matched = if pattern.contains('/') {
+                file.is_some_and(|(_, path)| glob_match(pattern, path))
+            } else {
+                file.is_some_and(|(stem, _)| glob_match(pattern, stem))
+                    || module.is_some_and(|module| glob_match(pattern, module))
+            };
+            if matched {
+                return *v;
+            }
+        }
+        self.v
+    }
+
+    /// Returns the most verbose level enabled for any module.
+    fn max_level(&self) -> LevelFilter {
+        let v = self.vmodule.iter().map(|(_, v)| *v).fold(self.v, i32::max);
+        match v {
+            i32::MIN..=0 => LevelFilter::INFO,
+            1 => LevelFilter::DEBUG,
+            _ => LevelFilter::TRACE,
+        }
+    }
+}
+
+impl GlogFilterHandle {
+    /// Sets the verbosity of modules that no `vmodule` pattern matches, as with
+    /// [`GlogOptions::with_verbosity`].
+    pub fn set_verbosity(&self, v: i32) {
+        self.update(|verbosity| Verbosity {
+            v,
+            vmodule: verbosity.vmodule.clone(),
+        });
+    }
+
+    /// Replaces the `vmodule` patterns, as with [`GlogOptions::with_vmodule`]. An empty
+    /// string removes every pattern.
+    ///
+    /// If `vmodule` cannot be parsed, the patterns are left unchanged.
+    pub fn set_vmodule(&self, vmodule: &str) -> Result<(), ConfigError> {
+        let vmodule = parse_vmodule(vmodule).ok_or_else(|| ConfigError::InvalidValue {
+            key: "vmodule".to_string(),
+            value: vmodule.to_string(),
+            expected: VMODULE,
+        })?;
+        self.update(|verbosity| Verbosity {
+            v: verbosity.v,
+            vmodule: vmodule.clone(),
+        });
+        Ok(())
+    }
+
+    /// Returns the verbosity of modules that no `vmodule` pattern matches.
+    pub fn verbosity(&self) -> i32 {
+        self.verbosity.load().v
+    }
+
+    fn update(&self, f: impl Fn(&Verbosity) -> Verbosity) {
+        self.verbosity.rcu(|verbosity| f(verbosity));
+        // Callsites cache whether they are enabled, and `tracing` caches the most verbose
+        // enabled level; both are recomputed from the new settings.
+        tracing::callsite::rebuild_interest_cache();
+    }
+}
+
+impl<S: Subscriber> Layer<S> for GlogFilter {
+    fn register_callsite(
+        &self,
+        metadata: &'static Metadata<'static>,
+    ) -> tracing::subscriber::Interest {
+        if self.is_enabled(metadata) {
+            tracing::subscriber::Interest::always()
+        } else {
+            tracing::subscriber::Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _: Context<'_, S>) -> bool {
+        self.is_enabled(metadata)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.max_level())
+    }
+}
+
+/// An error returned when glog options are invalid or cannot be applied.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// A setting has a value that cannot be parsed.
+    InvalidValue {
+        /// The name of the setting, such as `GLOG_v`.
+        key: String,
+        /// The invalid value.
+        value: String,
+        /// A description of the values that are accepted.
+        expected: &'static str,
+    },
+    /// A setting that `tracing-glog` does not know about.
+    UnknownFlag(String),
+    /// The log directory does not exist or is not a directory.
+    LogDir(PathBuf, io::Error),
+    /// A configuration file could not be read.
+    Read(PathBuf, io::Error),
+    /// A configuration file is invalid.
+    Parse {
+        /// The path to the offending key, such as `outputs[1].threshold`, or `.` if the error
+        /// is not about a particular key.
+        key: String,
+        /// A description of the problem.
+        message: String,
+    },
+    /// A global default subscriber has already been set.
+    AlreadyInitialized,
+}
+
+impl ConfigError {
+    /// Renames the setting an error refers to, such as from `v` to `GLOG_v`.
+    fn with_key(self, new_key: &str) -> Self {
+        match self {
+            ConfigError::InvalidValue {
+                value, expected, ..
+            } => ConfigError::InvalidValue {
+                key: new_key.to_string(),
+                value,
+                expected,
+            },
+            ConfigError::UnknownFlag(_) => ConfigError::UnknownFlag(new_key.to_string()),
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InvalidValue {
+                key,
+                value,
+                expected,
+            } => write!(
+                f,
+                "invalid value `{}` for `{}`: expected {}",
+                value, key, expected
+            ),
+            ConfigError::UnknownFlag(key) => write!(f, "unknown setting `{}`", key),
+            ConfigError::LogDir(dir, _) => {
+                write!(f, "unable to use `{}` as the log directory", dir.display())
+            }
+            ConfigError::Read(path, _) => {
+                write!(f, "unable to read configuration file `{}`", path.display())
+            }
+            ConfigError::Parse { key, message } => {
+                write!(f, "invalid configuration at `{}`: {}", key, message)
+            }
+            ConfigError::AlreadyInitialized => {
+                write!(f, "a global default subscriber has already been set")
+            }
+        }
+    }
+}
+
+impl error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ConfigError::LogDir(_, e) | ConfigError::Read(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) const BOOL: &str = "`true` or `false`";
+pub(crate) const SEVERITY: &str = "one of INFO, WARNING, ERROR, FATAL, or 0 to 3";
+pub(crate) const VMODULE: &str = "a comma-separated list of `<pattern>=<verbosity>`";
+
+pub(crate) fn check_log_dir(dir: &Path) -> Result<(), ConfigError> {
+    match std::fs::metadata(dir) {
+        Ok(metadata) if metadata.is_dir() => Ok(()),
+        Ok(_) => Err(ConfigError::LogDir(
+            dir.to_path_buf(),
+            io::Error::other("not a directory"),
+        )),
+        Err(e) => Err(ConfigError::LogDir(dir.to_path_buf(), e)),
+    }
+}
+
+pub(crate) fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "t" | "yes" | "y" | "1" => Some(true),
+        "false" | "f" | "no" | "n" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses a glog severity into the least severe level it enables. `INFO` includes glog's
+/// verbose logs, which are `DEBUG` and `TRACE` events.
+pub(crate) fn parse_severity(value: &str) -> Option<LevelFilter> {
+    match value.to_ascii_uppercase().as_str() {
+        "0" | "INFO" => Some(LevelFilter::TRACE),
+        "1" | "WARNING" => Some(LevelFilter::WARN),
+        "2" | "ERROR" => Some(LevelFilter::ERROR),
+        "3" | "FATAL" => Some(LevelFilter::OFF),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse_vmodule(value: &str) -> Option<Vec<(String, i32)>> {
+    value
+        .split(',')
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (pattern, v) = entry.split_once('=')?;
+            if pattern.is_empty() {
+                return None;
+            }
+            Some((pattern.to_string(), v.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Matches `text` against a pattern where `*` matches any run of characters and `?` matches
+/// any single character.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // The position of the last `*` in the pattern, and of the text it was matched against.
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::{
+        callsite::{Callsite, Identifier},
+        field::FieldSet,
+        metadata::Kind,
+        subscriber::Interest,
+    };
+
+    struct TestCallsite;
+
+    impl Callsite for TestCallsite {
+        fn set_interest(&self, _: Interest) {}
+
+        fn metadata(&self) -> &Metadata<'_> {
+            &METADATA
+        }
+    }
+
+    static CALLSITE: TestCallsite = TestCallsite;
+    static METADATA: Metadata<'static> = Metadata::new(
+        "event",
+        "yak",
+        Level::DEBUG,
+        Some("src/lib.rs"),
+        Some(1),
+        Some("yak"),
+        FieldSet::new(&[], Identifier(&CALLSITE)),
+        Kind::EVENT,
+    );
+
+    fn metadata<'a>(file: &'a str, module_path: &'a str) -> Metadata<'a> {
+        Metadata::new(
+            "event",
+            module_path,
+            Level::DEBUG,
+            Some(file),
+            Some(1),
+            Some(module_path),
+            FieldSet::new(&[], Identifier(&CALLSITE)),
+            Kind::EVENT,
+        )
+    }
+
+    fn verbosity(v: i32, vmodule: &str) -> Verbosity {
+        Verbosity {
+            v,
+            vmodule: parse_vmodule(vmodule).unwrap(),
+        }
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_match("shaving", "shaving"));
+        assert!(!glob_match("shaving", "shavings"));
+        assert!(!glob_match("shavings", "shaving"));
+
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "shaving"));
+        assert!(glob_match("yak*", "yak"));
+        assert!(glob_match("yak*", "yak_shave"));
+        assert!(glob_match("*shave", "yak_shave"));
+        assert!(glob_match("y*_*e", "yak_shave"));
+        assert!(glob_match("*a*a*", "banana"));
+        assert!(!glob_match("yak*", "a_yak"));
+        assert!(!glob_match("*shave", "yak_shaver"));
+
+        assert!(glob_match("y?k", "yak"));
+        assert!(glob_match("???", "yäk"));
+        assert!(!glob_match("?", ""));
+        assert!(!glob_match("y?k", "yk"));
+        assert!(!glob_match("y?k", "yaak"));
+        assert!(glob_match("src/*/y?k*", "src/bin/yak_shave"));
+    }
+
+    #[test]
+    fn vmodule_lists() {
+        assert_eq!(
+            parse_vmodule("shaving=2,yak*=-1"),
+            Some(vec![("shaving".to_string(), 2), ("yak*".to_string(), -1)])
+        );
+        assert_eq!(
+            parse_vmodule(",shaving=2,,"),
+            Some(vec![("shaving".to_string(), 2)])
+        );
+        assert_eq!(parse_vmodule(""), Some(vec![]));
+
+        assert_eq!(parse_vmodule("shaving"), None);
+        assert_eq!(parse_vmodule("=2"), None);
+        assert_eq!(parse_vmodule("shaving="), None);
+        assert_eq!(parse_vmodule("shaving=two"), None);
+        assert_eq!(parse_vmodule("shaving=2,yak"), None);
+    }
+
+    #[test]
+    fn vmodule_patterns_match_files_and_modules() {
+        let verbosity = verbosity(0, "shaving=1,src/bin/*=2,razor=3");
+
+        // A pattern without a `/` matches the file name or the last module path segment.
+        let file = metadata("src/shaving.rs", "yak::clippers");
+        assert_eq!(verbosity.of(&file), 1);
+        let module = metadata("src/lib.rs", "yak::razor");
+        assert_eq!(verbosity.of(&module), 3);
+        let parent_module = metadata("src/lib.rs", "razor::yak");
+        assert_eq!(verbosity.of(&parent_module), 0);
+
+        // A pattern with a `/` matches the path of the file without its extension.
+        let path = metadata("src/bin/shave.rs", "shave");
+        assert_eq!(verbosity.of(&path), 2);
+        let nested = metadata("crates/yak/src/bin/shave.rs", "shave");
+        assert_eq!(verbosity.of(&nested), 0);
+        let module_with_slash = metadata("src/main.rs", "bin");
+        assert_eq!(verbosity.of(&module_with_slash), 0);
+    }
+
+    #[test]
+    fn first_matching_vmodule_pattern_wins() {
+        let shaving = metadata("src/shaving.rs", "yak::shaving");
+
+        assert_eq!(verbosity(0, "shav*=1,shaving=2").of(&shaving), 1);
+        assert_eq!(verbosity(0, "shaving=2,shav*=1").of(&shaving), 2);
+        assert_eq!(verbosity(0, "src/*=3,shaving=2").of(&shaving), 3);
+        assert_eq!(verbosity(1, "razor=3").of(&shaving), 1);
+        assert_eq!(verbosity(2, "shaving=0").of(&shaving), 0);
+    }
+
+    #[test]
+    fn max_level_covers_every_vmodule_pattern() {
+        assert_eq!(verbosity(0, "").max_level(), LevelFilter::INFO);
+        assert_eq!(verbosity(-1, "").max_level(), LevelFilter::INFO);
+        assert_eq!(verbosity(0, "shaving=1").max_level(), LevelFilter::DEBUG);
+        assert_eq!(verbosity(1, "shaving=0").max_level(), LevelFilter::DEBUG);
+        assert_eq!(verbosity(0, "a=1,b=5").max_level(), LevelFilter::TRACE);
+    }
+
+    #[test]
+    fn severities() {
+        assert_eq!(parse_severity("0"), Some(LevelFilter::TRACE));
+        assert_eq!(parse_severity("info"), Some(LevelFilter::TRACE));
+        assert_eq!(parse_severity("1"), Some(LevelFilter::WARN));
+        assert_eq!(parse_severity("WARNING"), Some(LevelFilter::WARN));
+        assert_eq!(parse_severity("2"), Some(LevelFilter::ERROR));
+        assert_eq!(parse_severity("Error"), Some(LevelFilter::ERROR));
+        assert_eq!(parse_severity("3"), Some(LevelFilter::OFF));
+        assert_eq!(parse_severity("FATAL"), Some(LevelFilter::OFF));
+
+        assert_eq!(parse_severity("4"), None);
+        assert_eq!(parse_severity("-1"), None);
+        assert_eq!(parse_severity("WARN"), None);
+        assert_eq!(parse_severity(""), None);
+    }
+
+    #[test]
+    fn booleans() {
+        for value in ["true", "T", "yes", "Y", "1"] {
+            assert_eq!(parse_bool(value), Some(true), "{}", value);
+        }
+        for value in ["false", "F", "no", "N", "0"] {
+            assert_eq!(parse_bool(value), Some(false), "{}", value);
+        }
+        for value in ["", "2", "on", "off", "maybe"] {
+            assert_eq!(parse_bool(value), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        fn invalid(flag: &str, value: &str) -> (String, String, &'static str) {
+            match GlogOptions::default().set(flag, value) {
+                Err(ConfigError::InvalidValue {
+                    key,
+                    value,
+                    expected,
+                }) => (key, value, expected),
+                other => panic!("{}={}: unexpected {:?}", flag, value, other),
+            }
+        }
+
+        assert_eq!(
+            invalid("v", "loud"),
+            ("v".to_string(), "loud".to_string(), "an integer")
+        );
+        assert_eq!(invalid("v", "1.5").2, "an integer");
+        assert_eq!(invalid("vmodule", "shaving").2, VMODULE);
+        assert_eq!(invalid("minloglevel", "4").2, SEVERITY);
+        assert_eq!(invalid("minloglevel", "DEBUG").2, SEVERITY);
+        assert_eq!(invalid("stderrthreshold", "-1").2, SEVERITY);
+        assert_eq!(invalid("logtostderr", "maybe").2, BOOL);
+        assert_eq!(invalid("alsologtostderr", "").2, BOOL);
+        assert_eq!(invalid("log_prefix", "2").2, BOOL);
+        assert_eq!(invalid("max_log_size", "-1").2, "a non-negative integer");
+
+        assert!(matches!(
+            GlogOptions::default().set("logbuflevel", "0"),
+            Err(ConfigError::UnknownFlag(flag)) if flag == "logbuflevel"
+        ));
+    }
+
+    #[test]
+    fn environment_errors_name_the_variable() {
+        let error = ConfigError::InvalidValue {
+            key: "minloglevel".to_string(),
+            value: "9".to_string(),
+            expected: SEVERITY,
+        }
+        .with_key("GLOG_minloglevel");
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "invalid value `9` for `GLOG_minloglevel`: expected {}",
+                SEVERITY
+            )
+        );
+    }
+
+    #[test]
+    fn valid_values_are_applied() {
+        let mut options = GlogOptions::default();
+        options.set("v", "-2").unwrap();
+        options.set("minloglevel", "warning").unwrap();
+        options.set("logtostderr", "1").unwrap();
+        options.set("vmodule", "shaving=2").unwrap();
+        assert_eq!(options.v, -2);
+        assert_eq!(options.minloglevel, LevelFilter::WARN);
+        assert!(options.logtostderr);
+        assert_eq!(options.vmodule, vec![("shaving".to_string(), 2)]);
+    }
+
+    #[test]
+    fn options_from_the_environment() {
+        let vars = |vars: &'static [(&'static str, &'static str)]| {
+            move |key: &str| {
+                vars.iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, value)| OsString::from(value))
+            }
+        };
+        let options = GlogOptions::from_vars(vars(&[
+            ("GLOG_v", "2"),
+            ("GLOG_vmodule", "shaving=1"),
+            ("GLOG_log_dir", "/var/log/yaks"),
+            ("GLOG_alsologtostderr", "true"),
+            // Empty variables keep their default.
+            ("GLOG_minloglevel", ""),
+            // Flags that are not supported are ignored.
+            ("GLOG_logbuflevel", "-1"),
+        ]))
+        .unwrap();
+        assert_eq!(options.v, 2);
+        assert_eq!(options.vmodule, vec![("shaving".to_string(), 1)]);
+        assert_eq!(options.log_dir, Some(PathBuf::from("/var/log/yaks")));
+        assert!(options.alsologtostderr);
+        assert_eq!(options.minloglevel, LevelFilter::TRACE);
+
+        let error = GlogOptions::from_vars(vars(&[("GLOG_stderrthreshold", "9")])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "invalid value `9` for `GLOG_stderrthreshold`: expected {}",
+                SEVERITY
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn non_utf8_variables_are_rejected() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let error = GlogOptions::from_vars(|key| {
+            (key == "GLOG_log_dir").then(|| OsString::from_vec(vec![b'/', 0xff]))
+        })
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            ConfigError::InvalidValue { key, expected: "valid UTF-8", .. } if key == "GLOG_log_dir"
+        ));
+    }
+
+    #[test]
+    fn max_log_sizes_are_in_megabytes() {
+        let max_file_size = |max_log_size| {
+            GlogOptions::default()
+                .with_max_log_size(max_log_size)
+                .max_file_size()
+        };
+        assert_eq!(max_file_size(10), 10 << 20);
+        assert_eq!(max_file_size(0), 1 << 20);
+        assert_eq!(max_file_size(u64::MAX), u64::MAX);
+        assert_eq!(max_file_size(1 << 44), u64::MAX);
+    }
+}
@@ -0,0 +1,617 @@
+use crate::{
+    config::{check_log_dir, parse_severity, parse_vmodule, SEVERITY, VMODULE},
+    CachedUtcTime, ConfigError, Glog, GlogDialect, GlogFields, GlogFilter, LogFiles, Monotonic,
+    ShutdownGuard, TimeZone, Uptime, ZonedTime,
+};
+use serde::{de, Deserialize, Deserializer};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{
+    fmt::{format::Writer, time::FormatTime, Layer as FmtLayer},
+    layer::{Layer, SubscriberExt},
+    Registry,
+};
+
+/// A declarative description of a glog setup, typically loaded from a TOML file.
+///
+/// Every setting is optional. The formatter settings mirror the `with_*` methods of [`Glog`]
+/// and [`GlogFields`], the filter settings mirror glog's `--v`, `--vmodule` and
+/// `--minloglevel` flags (see [`GlogOptions`]), and each entry of `outputs` adds a
+/// destination with its own severity threshold. Without any outputs, everything is written to
+/// stderr.
+///
+/// ```toml
+/// dialect = "glog"            # glog, glog_with_year, abseil, klog or absl_py
+/// timer = "utc"               # utc, cached_utc, local, uptime or monotonic
+/// time_zone = "Europe/Berlin" # formats `utc` and `local` timestamps in this time zone
+/// thread_names = false        # defaults to the dialect's setting, as do the next two
+/// target = false
+/// trimmed_directory = true
+/// strip_prefix = "src/"
+/// strip_prefixes = ["/build/vendor", "/build"] # removed in addition to `strip_prefix`
+/// span_context = true
+/// log_prefix = true
+/// v = 0
+/// vmodule = "shaving=2"
+/// minloglevel = "INFO"
+/// redact = ["password", "token"]
+///
+/// [[outputs]]
+/// kind = "stderr"             # stderr, stdout or files
+/// threshold = "WARNING"
+/// ansi = true
+///
+/// [[outputs]]
+/// kind = "files"
+/// dir = "/var/log/yaks"
+/// program_name = "yak-shave"
+/// max_size_mb = 100           # starts a new file after 100 MiB
+/// flush_level = "ERROR"
+/// max_buffer_age_secs = 30
+/// ```
+///
+/// The `local` timer looks up the local offset from UTC once, when [`GlogConfig::init`] is
+/// called, and keeps using it, so its timestamps do not follow daylight saving time
+/// transitions. Setting `time_zone` as well formats them in that time zone, following its
+/// transitions, as with the `utc` timer.
+///
+/// Unknown keys are rejected, and every error names the key it is about, such as
+/// `outputs[1].threshold`. Other formats, such as YAML, can be loaded with
+/// [`GlogConfig::from_deserializer`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use tracing_glog::GlogConfig;
+///
+/// let _guard = GlogConfig::from_file("glog.toml")?.init()?;
+/// # Ok::<(), tracing_glog::ConfigError>(())
+/// ```
+///
+/// This type is available with the `config-file` feature.
+///
+/// [`GlogOptions`]: crate::GlogOptions
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GlogConfig {
+    dialect: GlogDialect,
+    timer: TimerKind,
+    time_zone: Option<String>,
+    thread_names: Option<bool>,
+    target: Option<bool>,
+    trimmed_directory: Option<bool>,
+    strip_prefix: Option<String>,
+    strip_prefixes: Vec<String>,
+    span_context: bool,
+    log_prefix: bool,
+    v: i32,
+    vmodule: VModule,
+    minloglevel: Severity,
+    redact: Vec<String>,
+    outputs: Vec<OutputConfig>,
+}
+
+/// One destination of a [`GlogConfig`].
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OutputConfig {
+    kind: OutputKind,
+    #[serde(default)]
+    threshold: Severity,
+    ansi: Option<bool>,
+    dir: Option<PathBuf>,
+    program_name: Option<String>,
+    max_size_mb: Option<u64>,
+    flush_level: Option<Severity>,
+    max_buffer_age_secs: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum TimerKind {
+    #[default]
+    Utc,
+    CachedUtc,
+    Local,
+    Uptime,
+    Monotonic,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum OutputKind {
+    Stderr,
+    Stdout,
+    Files,
+}
+
+impl OutputKind {
+    fn name(self) -> &'static str {
+        match self {
+            OutputKind::Stderr => "stderr",
+            OutputKind::Stdout => "stdout",
+            OutputKind::Files => "files",
+        }
+    }
+}
+
+/// A glog severity, by name or number.
+#[derive(Clone, Copy, Debug)]
+struct Severity(LevelFilter);
+
+/// A parsed `vmodule` list.
+#[derive(Clone, Debug, Default)]
+struct VModule(Vec<(String, i32)>);
+
+/// A timer chosen at runtime.
+struct DynTimer(Box<dyn FormatTime + Send + Sync>);
+
+impl Default for GlogConfig {
+    fn default() -> Self {
+        GlogConfig {
+            dialect: GlogDialect::default(),
+            timer: TimerKind::default(),
+            time_zone: None,
+            thread_names: None,
+            target: None,
+            trimmed_directory: None,
+            strip_prefix: None,
+            strip_prefixes: Vec::new(),
+            span_context: true,
+            log_prefix: true,
+            v: 0,
+            vmodule: VModule::default(),
+            minloglevel: Severity::default(),
+            redact: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+}
+
+impl GlogConfig {
+    /// Reads a TOML configuration file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let contents =
+            fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+        GlogConfig::from_toml(&contents)
+    }
+
+    /// Parses a TOML configuration.
+    pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        GlogConfig::from_deserializer(toml::Deserializer::new(toml))
+    }
+
+    /// Deserializes a configuration in any format supported by `serde`, recording the path to
+    /// the offending key on errors.
+    pub fn from_deserializer<'de, D>(deserializer: D) -> Result<Self, ConfigError>
+    where
+        D: Deserializer<'de>,
+    {
+        let config: GlogConfig =
+            serde_path_to_error::deserialize(deserializer).map_err(|e| ConfigError::Parse {
+                key: e.path().to_string(),
+                message: e.inner().to_string(),
+            })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the settings that depend on each other.
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.time_zone.is_some() && !matches!(self.timer, TimerKind::Utc | TimerKind::Local) {
+            return Err(ConfigError::Parse {
+                key: "time_zone".to_string(),
+                message: "a time zone can only be used with the `utc` and `local` timers"
+                    .to_string(),
+            });
+        }
+        for (i, output) in self.outputs.iter().enumerate() {
+            let files_only = [
+                ("dir", output.dir.is_some()),
+                ("program_name", output.program_name.is_some()),
+                ("max_size_mb", output.max_size_mb.is_some()),
+                ("flush_level", output.flush_level.is_some()),
+                ("max_buffer_age_secs", output.max_buffer_age_secs.is_some()),
+            ];
+            let unsupported = match output.kind {
+                OutputKind::Files if output.dir.is_none() => {
+                    return Err(ConfigError::Parse {
+                        key: format!("outputs[{}]", i),
+                        message: "missing field `dir`".to_string(),
+                    });
+                }
+                OutputKind::Files => output.ansi.is_some().then_some("ansi"),
+                OutputKind::Stderr | OutputKind::Stdout => files_only
+                    .iter()
+                    .find(|(_, is_set)| *is_set)
+                    .map(|(key, _)| *key),
+            };
+            if let Some(key) = unsupported {
+                return Err(ConfigError::Parse {
+                    key: format!("outputs[{}].{}", i, key),
+                    message: format!("not supported by `{}` outputs", output.kind.name()),
+                });
+            }
+            if output.max_size_mb.is_some() && output.max_file_size().is_none() {
+                return Err(ConfigError::Parse {
+                    key: format!("outputs[{}].max_size_mb", i),
+                    message: format!("expected at most {} megabytes", u64::MAX >> 20),
+                });
+            }
+            if let Some(Severity(LevelFilter::OFF)) = output.flush_level {
+                return Err(ConfigError::Parse {
+                    key: format!("outputs[{}].flush_level", i),
+                    message: "expected one of INFO, WARNING or ERROR".to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the filter implementing `v`, `vmodule` and `minloglevel`.
+    pub fn filter(&self) -> GlogFilter {
+        GlogFilter::new(self.v, self.vmodule.0.clone(), self.minloglevel.0)
+    }
+
+    /// Builds the subscriber described by this configuration and sets it as the global
+    /// default.
+    ///
+    /// The returned guard calls [`shutdown`](crate::shutdown) when dropped, so that buffered
+    /// lines reach the log files.
+    pub fn init(&self) -> Result<ShutdownGuard, ConfigError> {
+        let time_zone = match &self.time_zone {
+            Some(name) => Some(TimeZone::from_name(name).map_err(|e| ConfigError::Parse {
+                key: "time_zone".to_string(),
+                message: e.to_string(),
+            })?),
+            None => None,
+        };
+
+        let default_output = [OutputConfig {
+            kind: OutputKind::Stderr,
+            threshold: Severity::default(),
+            ansi: None,
+            dir: None,
+            program_name: None,
+            max_size_mb: None,
+            flush_level: None,
+            max_buffer_age_secs: None,
+        }];
+        let outputs = if self.outputs.is_empty() {
+            &default_output[..]
+        } else {
+            &self.outputs
+        };
+
+        let mut layers = Vec::with_capacity(outputs.len());
+        for (i, output) in outputs.iter().enumerate() {
+            let format = self.event_format(time_zone.as_ref());
+            let fields = GlogFields::default()
+                .with_dialect(self.dialect)
+                .with_redacted_fields(self.redact.iter().cloned());
+            let layer = FmtLayer::default()
+                .event_format(format)
+                .fmt_fields(fields)
+                .with_ansi(output.ansi.unwrap_or(false));
+            let layer = match output.kind {
+                OutputKind::Stderr => layer.with_writer(io::stderr).boxed(),
+                OutputKind::Stdout => layer.with_writer(io::stdout).boxed(),
+                OutputKind::Files => {
+                    let dir = output.dir.clone().unwrap_or_default();
+                    check_log_dir(&dir).map_err(|e| match e {
+                        ConfigError::LogDir(dir, e) => ConfigError::Parse {
+                            key: format!("outputs[{}].dir", i),
+                            message: format!("unable to use `{}`: {}", dir.display(), e),
+                        },
+                        e => e,
+                    })?;
+                    layer.with_writer(self.log_files(output, dir)).boxed()
+                }
+            };
+            layers.push(layer.with_filter(output.threshold.0));
+        }
+
+        let subscriber = Registry::default().with(self.filter()).with(layers);
+        tracing::subscriber::set_global_default(subscriber)
+            .map_err(|_| ConfigError::AlreadyInitialized)?;
+        Ok(ShutdownGuard::new())
+    }
+
+    fn event_format(&self, time_zone: Option<&TimeZone>) -> Glog<DynTimer> {
+        let format = self.dialect.time_format();
+        let timer: Box<dyn FormatTime + Send + Sync> = match (self.timer, time_zone) {
+            (TimerKind::Utc | TimerKind::Local, Some(zone)) => {
+                Box::new(ZonedTime::new(zone.clone(), format))
+            }
+            (TimerKind::Utc, None) => Box::new(self.dialect.utc_time()),
+            (TimerKind::CachedUtc, _) if self.dialect == GlogDialect::GlogWithYear => {
+                Box::new(CachedUtcTime::with_year())
+            }
+            (TimerKind::CachedUtc, _) => Box::new(CachedUtcTime::new()),
+            (TimerKind::Local, _) => Box::new(self.dialect.local_time().with_startup_offset()),
+            (TimerKind::Uptime, _) => Box::new(Uptime::since_process_start()),
+            (TimerKind::Monotonic, _) => Box::new(Monotonic::new()),
+        };
+
+        let mut glog = Glog::default()
+            .with_dialect(self.dialect)
+            .with_timer(DynTimer(timer))
+            .with_strip_prefixes(
+                self.strip_prefix
+                    .iter()
+                    .chain(&self.strip_prefixes)
+                    .cloned(),
+            )
+            .with_span_context(self.span_context)
+            .with_prefix(self.log_prefix);
+        // Settings that are not given keep the dialect's defaults.
+        if let Some(thread_names) = self.thread_names {
+            glog = glog.with_thread_names(thread_names);
+        }
+        if let Some(target) = self.target {
+            glog = glog.with_target(target);
+        }
+        if let Some(trimmed_directory) = self.trimmed_directory {
+            glog = glog.with_trimmed_directory(trimmed_directory);
+        }
+        glog
+    }
+
+    fn log_files(&self, output: &OutputConfig, dir: PathBuf) -> LogFiles {
+        let mut log_files = LogFiles::new(dir);
+        if let Some(program_name) = &output.program_name {
+            log_files = log_files.with_program_name(program_name);
+        }
+        if let Some(max_file_size) = output.max_file_size() {
+            log_files = log_files.with_max_file_size(max_file_size);
+        }
+        if let Some(level) = output.flush_level.and_then(|s| s.0.into_level()) {
+            log_files = log_files.with_flush_level(level);
+        }
+        if let Some(secs) = output.max_buffer_age_secs {
+            log_files = log_files.with_max_buffer_age(Duration::from_secs(secs));
+        }
+        log_files
+    }
+}
+
+impl OutputConfig {
+    /// Returns `max_size_mb` in bytes, or `None` if it is unset or does not fit in a `u64`.
+    fn max_file_size(&self) -> Option<u64> {
+        self.max_size_mb?.max(1).checked_mul(1 << 20)
+    }
+}
+
+impl Default for Severity {
+    fn default() -> Self {
+        Severity(LevelFilter::TRACE)
+    }
+}
+
+impl<'de> Deserialize<'de> for Severity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = Severity;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(SEVERITY)
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Severity, E> {
+                parse_severity(value)
+                    .map(Severity)
+                    .ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Severity, E> {
+                parse_severity(&value.to_string())
+                    .map(Severity)
+                    .ok_or_else(|| E::invalid_value(de::Unexpected::Signed(value), &self))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Severity, E> {
+                parse_severity(&value.to_string())
+                    .map(Severity)
+                    .ok_or_else(|| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for VModule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        parse_vmodule(&value)
+            .map(VModule)
+            .ok_or_else(|| de::Error::invalid_value(de::Unexpected::Str(&value), &VMODULE))
+    }
+}
+
+impl FormatTime for DynTimer {
+    fn format_time(&self, writer: &mut Writer<'_>) -> fmt::Result {
+        self.0.format_time(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::OffsetDateTime;
+
+    #[test]
+    fn strip_prefixes_are_combined() {
+        let config = GlogConfig::from_toml(
+            r#"
+            strip_prefix = "src/"
+            strip_prefixes = ["/build", "/build/vendor"]
+            "#,
+        )
+        .unwrap();
+        let glog = config.event_format(None);
+        assert_eq!(glog.strip_prefixes, ["/build/vendor", "/build", "src/"]);
+
+        let config = GlogConfig::from_toml(r#"strip_prefixes = ["/build"]"#).unwrap();
+        assert_eq!(config.event_format(None).strip_prefixes, ["/build"]);
+
+        let config = GlogConfig::from_toml("").unwrap();
+        assert!(config.event_format(None).strip_prefixes.is_empty());
+    }
+
+    #[test]
+    fn strip_prefixes_must_be_a_list() {
+        match GlogConfig::from_toml(r#"strip_prefixes = "/build""#) {
+            Err(ConfigError::Parse { key, .. }) => assert_eq!(key, "strip_prefixes"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    /// Returns the key and message of the error that loading `toml` fails with.
+    fn error(toml: &str) -> (String, String) {
+        match GlogConfig::from_toml(toml) {
+            Err(ConfigError::Parse { key, message }) => (key, message),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors_name_the_key() {
+        let (key, message) = error(
+            r#"
+            [[outputs]]
+            kind = "stderr"
+
+            [[outputs]]
+            kind = "stdout"
+            threshold = "LOUD"
+            "#,
+        );
+        assert_eq!(key, "outputs[1].threshold");
+        assert!(message.contains(SEVERITY), "{}", message);
+
+        let (key, message) = error("v = \"loud\"");
+        assert_eq!(key, "v");
+        assert!(message.contains("invalid type"), "{}", message);
+
+        assert_eq!(error("vmodule = \"shaving\"").0, "vmodule");
+        assert_eq!(error("timer = \"sundial\"").0, "timer");
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let (key, message) = error("colour = true");
+        assert_eq!(key, "colour");
+        assert!(message.contains("unknown field `colour`"), "{}", message);
+
+        let (key, message) = error(
+            r#"
+            [[outputs]]
+            kind = "stderr"
+            colour = true
+            "#,
+        );
+        assert_eq!(key, "outputs[0].colour");
+        assert!(message.contains("unknown field `colour`"), "{}", message);
+    }
+
+    #[test]
+    fn files_outputs_are_checked() {
+        let (key, message) = error(
+            r#"
+            [[outputs]]
+            kind = "files"
+            "#,
+        );
+        assert_eq!(key, "outputs[0]");
+        assert_eq!(message, "missing field `dir`");
+
+        let (key, message) = error(
+            r#"
+            [[outputs]]
+            kind = "stderr"
+
+            [[outputs]]
+            kind = "files"
+            dir = "/var/log/yaks"
+            ansi = true
+            "#,
+        );
+        assert_eq!(key, "outputs[1].ansi");
+        assert_eq!(message, "not supported by `files` outputs");
+
+        let (key, message) = error(
+            r#"
+            [[outputs]]
+            kind = "stdout"
+            dir = "/var/log/yaks"
+            "#,
+        );
+        assert_eq!(key, "outputs[0].dir");
+        assert_eq!(message, "not supported by `stdout` outputs");
+
+        let (key, _) = error(
+            r#"
+            [[outputs]]
+            kind = "files"
+            dir = "/var/log/yaks"
+            flush_level = "FATAL"
+            "#,
+        );
+        assert_eq!(key, "outputs[0].flush_level");
+    }
+
+    #[test]
+    fn max_size_must_fit_in_bytes() {
+        let toml = |max_size_mb: u64| {
+            format!(
+                "[[outputs]]\nkind = \"files\"\ndir = \"/var/log/yaks\"\nmax_size_mb = {}",
+                max_size_mb
+            )
+        };
+        let config = GlogConfig::from_toml(&toml(u64::MAX >> 20)).unwrap();
+        assert_eq!(
+            config.outputs[0].max_file_size(),
+            Some(u64::MAX >> 20 << 20)
+        );
+        let config = GlogConfig::from_toml(&toml(0)).unwrap();
+        assert_eq!(config.outputs[0].max_file_size(), Some(1 << 20));
+
+        let (key, _) = error(&toml((u64::MAX >> 20) + 1));
+        assert_eq!(key, "outputs[0].max_size_mb");
+        let (key, _) = error(&toml(i64::MAX as u64));
+        assert_eq!(key, "outputs[0].max_size_mb");
+    }
+
+    #[test]
+    fn time_zones_apply_to_the_utc_and_local_timers() {
+        for timer in ["utc", "local"] {
+            let toml = format!("timer = \"{}\"\ntime_zone = \"Asia/Riyadh\"", timer);
+            GlogConfig::from_toml(&toml).unwrap();
+        }
+        let (key, _) = error("timer = \"uptime\"\ntime_zone = \"Asia/Riyadh\"");
+        assert_eq!(key, "time_zone");
+
+        // A zone three hours ahead of UTC, so that the hour differs from UTC's.
+        let zone = TimeZone::from_posix("AST-3").unwrap();
+        let config = GlogConfig::from_toml("timer = \"local\"\ntime_zone = \"AST-3\"").unwrap();
+        let glog = config.event_format(Some(&zone));
+        let before = OffsetDateTime::now_utc();
+        let mut timestamp = String::new();
+        glog.timer
+            .format_time(&mut Writer::new(&mut timestamp))
+            .unwrap();
+        let after = OffsetDateTime::now_utc();
+
+        let hour: u8 = timestamp[5..7].parse().unwrap();
+        let expected = [before, after].map(|utc| (utc.hour() + 3) % 24);
+        assert!(expected.contains(&hour), "{} at {}", timestamp, before);
+    }
+}
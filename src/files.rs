@@ -0,0 +1,631 @@
+use crate::shutdown::{self, Flush, Stage};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError, Weak,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use time::{macros::format_description, OffsetDateTime};
+use tracing::{Level, Metadata};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Writes log lines to glog-style per-severity log files.
+///
+/// Like glog, `LogFiles` keeps one file for each of the `INFO`, `WARNING` and `ERROR`
+/// severities, and each file receives the lines of its own severity and above: the `INFO`
+/// file holds every line, while the `ERROR` file only holds errors. `TRACE` and `DEBUG`
+/// events are written to the `INFO` file. Files are created the first time they are written
+/// to, in a directory that must already exist, and are named
+///
+/// ```text
+/// <program>.<host>.<user>.log.<SEVERITY>.<yyyymmdd>-<hhmmss>.<pid>
+/// ```
+///
+/// If a file with that name already exists, for instance because the previous file filled
+/// up within the same second, a numeric suffix (`.1`, `.2`, …) is added to the name, so that
+/// every file is new.
+///
+/// When a file grows past the maximum file size (see [`LogFiles::with_max_file_size`]), a new
+/// one is started. On Unix, a `<program>.<SEVERITY>` symlink points at the latest file of
+/// each severity.
+/// Each file starts with glog's header, which records when and where it was created.
+///
+/// If a file cannot be created, for instance because the directory is missing or the disk is
+/// full, the write returns the error and the line is dropped. Creating the file is retried
+/// after a delay that doubles with every failure, from 100 milliseconds up to 10 seconds;
+/// lines written before the next attempt are dropped as well.
+///
+/// # Buffering
+///
+/// Like glog, `LogFiles` buffers routine lines and writes severe ones immediately. A line at
+/// or above the flush level (`WARN` by default, see [`LogFiles::with_flush_level`]) flushes
+/// every file it is written to, together with the lines buffered before it. Lower-severity
+/// lines are buffered until a flush, or until the oldest buffered line is older than the
+/// maximum buffer age (30 seconds by default, see [`LogFiles::with_max_buffer_age`]). The age
+/// is checked when a line is written and, as with glog's `--logbufsecs`, by a background
+/// thread, so lines do not stay buffered in an idle process. Hold a [`ShutdownGuard`] in
+/// `main` so that buffered lines are written before the process exits.
+///
+/// [`flush`] and [`shutdown`] reach every `LogFiles`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use tracing_glog::{Glog, GlogFields, LogFiles, ShutdownGuard};
+///
+/// let _shutdown = ShutdownGuard::new();
+/// tracing_subscriber::fmt()
+///     .with_writer(LogFiles::new("/tmp").with_program_name("yak-shave"))
+///     .with_ansi(false)
+///     .event_format(Glog::default())
+///     .fmt_fields(GlogFields::default())
+///     .init();
+/// ```
+///
+/// [`flush`]: crate::flush
+/// [`shutdown`]: crate::shutdown
+/// [`ShutdownGuard`]: crate::ShutdownGuard
+#[derive(Debug)]
+pub struct LogFiles {
+    dir: PathBuf,
+    program_name: String,
+    flush_severity: Severity,
+    max_buffer_age: Duration,
+    max_file_size: u64,
+    files: Arc<Files>,
+}
+
+/// The writer returned by [`LogFiles`] for one event.
+#[derive(Debug)]
+pub struct LogFilesWriter<'a> {
+    files: &'a LogFiles,
+    severity: Severity,
+}
+
+/// The glog severities that get a file of their own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// The open files, shared with the flush registry.
+#[derive(Debug, Default)]
+struct Files {
+    slots: [Mutex<Slot>; 3],
+    registered: AtomicBool,
+}
+
+#[derive(Debug, Default)]
+enum Slot {
+    #[default]
+    Unopened,
+    Open {
+        file: BufWriter<File>,
+        /// When the file was last flushed, or `None` if nothing is buffered.
+        buffered_since: Option<Instant>,
+        /// The number of bytes written to the file.
+        size: u64,
+    },
+    /// The file could not be created. The error has been returned to the writer, and lines
+    /// are dropped until creating the file is retried at `retry_at`.
+    Failed {
+        retry_at: Instant,
+        /// The delay before the attempt after that one, if it fails too.
+        backoff: Duration,
+    },
+}
+
+/// The delay before creating a file is retried after the first failure.
+const MIN_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+/// The longest delay between attempts to create a file.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(10);
+
+impl LogFiles {
+    /// Returns a writer that creates log files in `dir`, named after the running program.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        LogFiles {
+            dir: dir.into(),
+            program_name: program_name(),
+            flush_severity: Severity::Warning,
+            max_buffer_age: Duration::from_secs(30),
+            max_file_size: 1800 << 20,
+            files: Arc::default(),
+        }
+    }
+
+    /// Sets the program name that log files are named after.
+    pub fn with_program_name(self, program_name: impl Into<String>) -> Self {
+        LogFiles {
+            program_name: program_name.into(),
+            ..self
+        }
+    }
+
+    /// Sets the least severe level that is written to disk immediately, like glog's
+    /// `--logbuflevel`. Lines of lower severity are buffered.
+    ///
+    /// Defaults to `WARN`. Since `TRACE`, `DEBUG` and `INFO` share the `INFO` file, setting
+    /// any of them disables buffering.
+    pub fn with_flush_level(self, level: Level) -> Self {
+        LogFiles {
+            flush_severity: Severity::from(level),
+            ..self
+        }
+    }
+
+    /// Sets how long lines may stay buffered before they are flushed, like glog's
+    /// `--logbufsecs`. Defaults to 30 seconds.
+    pub fn with_max_buffer_age(self, max_buffer_age: Duration) -> Self {
+        LogFiles {
+            max_buffer_age,
+            ..self
+        }
+    }
+
+    /// Sets the size in bytes after which a new log file is started, like glog's
+    /// `--max_log_size` (which is in megabytes). Defaults to 1800 MiB.
+    pub fn with_max_file_size(self, max_file_size: u64) -> Self {
+        LogFiles {
+            max_file_size,
+            ..self
+        }
+    }
+
+    /// Returns the writer for a line of the given level.
+    pub(crate) fn writer_for(&self, level: Level) -> LogFilesWriter<'_> {
+        LogFilesWriter {
+            files: self,
+            severity: Severity::from(level),
+        }
+    }
+
+    fn write_line(&self, severity: Severity, line: &[u8]) -> io::Result<()> {
+        let mut result = Ok(());
+        for file_severity in Severity::ALL {
+            if file_severity > severity {
+                break;
+            }
+            let mut slot = self.files.slots[file_severity as usize]
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let backoff = match *slot {
+                Slot::Unopened => Some(MIN_RETRY_BACKOFF),
+                Slot::Failed { retry_at, backoff } if Instant::now() >= retry_at => Some(backoff),
+                // The line is dropped until the next attempt.
+                Slot::Failed { .. } => continue,
+                Slot::Open { .. } => None,
+            };
+            if let Some(backoff) = backoff {
+                match self.open(file_severity) {
+                    Ok(opened) => *slot = opened,
+                    Err(e) => {
+                        *slot = Slot::Failed {
+                            retry_at: Instant::now() + backoff,
+                            backoff: (backoff * 2).min(MAX_RETRY_BACKOFF),
+                        };
+                        result = Err(e);
+                        continue;
+                    }
+                }
+            }
+            if let Slot::Open {
+                file,
+                buffered_since,
+                size,
+            } = &mut *slot
+            {
+                let oldest = *buffered_since.get_or_insert_with(Instant::now);
+                let mut written = file.write_all(line);
+                *size += line.len() as u64;
+                if severity >= self.flush_severity || oldest.elapsed() >= self.max_buffer_age {
+                    written = written.and_then(|()| file.flush());
+                    *buffered_since = None;
+                }
+                if *size >= self.max_file_size {
+                    // Dropping the file flushes it; the next line starts a new one.
+                    *slot = Slot::Unopened;
+                }
+                if let Err(e) = written {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    fn open(&self, severity: Severity) -> io::Result<Slot> {
+        if !self.files.registered.swap(true, Ordering::Relaxed) {
+            let files: Arc<dyn Flush> = self.files.clone();
+            shutdown::register(Stage::File, Arc::downgrade(&files));
+
+            let files = Arc::downgrade(&self.files);
+            let max_age = self.max_buffer_age;
+            let _ = thread::Builder::new()
+                .name("glog-files".into())
+                .spawn(move || Files::flush_old_buffers(files, max_age));
+        }
+
+        let file = self.create(severity).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "could not create the {} log file in {}: {}",
+                    severity.name(),
+                    self.dir.display(),
+                    e
+                ),
+            )
+        })?;
+        Ok(Slot::Open {
+            file: BufWriter::new(file),
+            buffered_since: None,
+            size: 0,
+        })
+    }
+
+    fn create(&self, severity: Severity) -> io::Result<File> {
+        let now = OffsetDateTime::now_utc();
+        let host = hostname();
+        let timestamp = now
+            .format(format_description!(
+                "[year][month][day]-[hour][minute][second]"
+            ))
+            .map_err(io::Error::other)?;
+        let file_name = format!(
+            "{}.{}.{}.log.{}.{}.{}",
+            self.program_name,
+            host,
+            user_name(),
+            severity.name(),
+            timestamp,
+            std::process::id()
+        );
+        // Files started within the same second would share a name, so later ones get a
+        // numeric suffix rather than being appended to an existing file.
+        let (file_name, mut file) = (0..)
+            .map(|n| match n {
+                0 => file_name.clone(),
+                n => format!("{}.{}", file_name, n),
+            })
+            .find_map(|file_name| {
+                let path = self.dir.join(&file_name);
+                match OpenOptions::new().write(true).create_new(true).open(path) {
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => None,
+                    opened => Some(opened.map(|file| (file_name, file))),
+                }
+            })
+            .expect("the range of suffixes is unbounded")?;
+
+        let created_at = now
+            .format(format_description!(
+                "[year]/[month]/[day] [hour]:[minute]:[second]"
+            ))
+            .map_err(io::Error::other)?;
+        write!(
+            file,
+            "Log file created at: {}\n\
+             Running on machine: {}\n\
+             Log line format: [IWEF]mmdd hh:mm:ss.uuuuuu threadid file:line] msg\n",
+            created_at, host
+        )?;
+
+        let link = self
+            .dir
+            .join(format!("{}.{}", self.program_name, severity.name()));
+        symlink(Path::new(&file_name), &link);
+        Ok(file)
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogFiles {
+    type Writer = LogFilesWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogFilesWriter {
+            files: self,
+            severity: Severity::Info,
+        }
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        self.writer_for(*meta.level())
+    }
+}
+
+impl<'a> Write for LogFilesWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.files.write_line(self.severity, buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Flush::flush(&*self.files.files, Level::TRACE);
+        Ok(())
+    }
+}
+
+impl Files {
+    /// Flushes files whose oldest buffered line has waited for longer than `max_age`, until
+    /// the files are dropped.
+    fn flush_old_buffers(files: Weak<Files>, max_age: Duration) {
+        let mut wait = max_age;
+        loop {
+            // A maximum age of zero would otherwise keep this thread busy.
+            thread::sleep(wait.max(Duration::from_millis(10)));
+            let Some(files) = files.upgrade() else {
+                return;
+            };
+            wait = max_age;
+            for slot in &files.slots {
+                let mut slot = slot.lock().unwrap_or_else(PoisonError::into_inner);
+                if let Slot::Open {
+                    file,
+                    buffered_since: buffered_since @ Some(_),
+                    ..
+                } = &mut *slot
+                {
+                    let age = buffered_since.map_or(Duration::ZERO, |oldest| oldest.elapsed());
+                    if age >= max_age {
+                        let _ = file.flush();
+                        *buffered_since = None;
+                    } else {
+                        wait = wait.min(max_age - age);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Flush for Files {
+    fn flush(&self, min_level: Level) {
+        let min_severity = Severity::from(min_level);
+        for severity in Severity::ALL {
+            if severity < min_severity {
+                continue;
+            }
+            let mut slot = self.slots[severity as usize]
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if let Slot::Open {
+                file,
+                buffered_since,
+                ..
+            } = &mut *slot
+            {
+                let _ = file.flush();
+                *buffered_since = None;
+            }
+        }
+    }
+
+    fn shutdown(&self) {
+        for slot in &self.slots {
+            let mut slot = slot.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Slot::Open {
+                file,
+                buffered_since,
+                ..
+            } = &mut *slot
+            {
+                let _ = file.flush();
+                let _ = file.get_ref().sync_all();
+                *buffered_since = None;
+            }
+        }
+    }
+}
+
+impl Severity {
+    const ALL: [Severity; 3] = [Severity::Info, Severity::Warning, Severity::Error];
+
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Info => "INFO",
+            Severity::Warning => "WARNING",
+            Severity::Error => "ERROR",
+        }
+    }
+}
+
+impl From<Level> for Severity {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::ERROR => Severity::Error,
+            Level::WARN => Severity::Warning,
+            _ => Severity::Info,
+        }
+    }
+}
+
+/// Returns the basename of the running program, as glog names its log files.
+fn program_name() -> String {
+    std::env::args_os()
+        .next()
+        .as_deref()
+        .map(Path::new)
+        .and_then(Path::file_name)
+        .and_then(|name| name.to_str())
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+fn user_name() -> String {
+    ["USER", "USERNAME"]
+        .into_iter()
+        .find_map(|var| std::env::var(var).ok().filter(|user| !user.is_empty()))
+        .unwrap_or_else(|| "invalid-user".to_string())
+}
+
+#[cfg(unix)]
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: `buf` is valid for writes of `buf.len()` bytes.
+    let ret = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    match std::str::from_utf8(&buf[..len]) {
+        Ok(host) if ret == 0 && !host.is_empty() => host.to_string(),
+        _ => "(unknown)".to_string(),
+    }
+}
+
+#[cfg(not(unix))]
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_else(|_| "(unknown)".to_string())
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) {
+    // The link is a convenience, so failing to update it is not an error.
+    let _ = std::fs::remove_file(link);
+    let _ = std::os::unix::fs::symlink(target, link);
+}
+
+#[cfg(not(unix))]
+fn symlink(_: &Path, _: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{lines_in, temp_dir};
+    use std::fs;
+
+    #[test]
+    fn routine_lines_wait_for_a_severe_one() {
+        let dir = temp_dir("flush-level");
+        let files = LogFiles::new(&dir)
+            .with_program_name("yaks")
+            .with_max_buffer_age(Duration::from_secs(3600));
+        files.writer_for(Level::INFO).write_all(b"a\n").unwrap();
+        files.writer_for(Level::DEBUG).write_all(b"b\n").unwrap();
+        assert_eq!(lines_in(&dir, "INFO"), "");
+
+        files.writer_for(Level::WARN).write_all(b"c\n").unwrap();
+        assert_eq!(lines_in(&dir, "INFO"), "a\nb\nc\n");
+        assert_eq!(lines_in(&dir, "WARNING"), "c\n");
+
+        files.writer_for(Level::ERROR).write_all(b"d\n").unwrap();
+        assert_eq!(lines_in(&dir, "INFO"), "a\nb\nc\nd\n");
+        assert_eq!(lines_in(&dir, "WARNING"), "c\nd\n");
+        assert_eq!(lines_in(&dir, "ERROR"), "d\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn the_flush_level_is_configurable() {
+        let dir = temp_dir("info-flush-level");
+        let files = LogFiles::new(&dir)
+            .with_program_name("yaks")
+            .with_flush_level(Level::ERROR)
+            .with_max_buffer_age(Duration::from_secs(3600));
+        files.writer_for(Level::WARN).write_all(b"a\n").unwrap();
+        assert_eq!(lines_in(&dir, "INFO"), "");
+        assert_eq!(lines_in(&dir, "WARNING"), "");
+        files.writer_for(Level::ERROR).write_all(b"b\n").unwrap();
+        assert_eq!(lines_in(&dir, "WARNING"), "a\nb\n");
+
+        let files = files.with_flush_level(Level::INFO);
+        files.writer_for(Level::INFO).write_all(b"c\n").unwrap();
+        assert_eq!(lines_in(&dir, "INFO"), "a\nb\nc\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn old_buffers_are_flushed_when_idle() {
+        let dir = temp_dir("max-age");
+        let files = LogFiles::new(&dir)
+            .with_program_name("yaks")
+            .with_max_buffer_age(Duration::from_millis(50));
+        files.writer_for(Level::INFO).write_all(b"a\n").unwrap();
+        assert_eq!(lines_in(&dir, "INFO"), "");
+
+        let start = Instant::now();
+        while lines_in(&dir, "INFO").is_empty() {
+            assert!(start.elapsed() < Duration::from_secs(10), "never flushed");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(lines_in(&dir, "INFO"), "a\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotations_within_a_second_start_new_files() {
+        let dir = temp_dir("rotations");
+        let files = LogFiles::new(&dir)
+            .with_program_name("yaks")
+            .with_max_file_size(1);
+        for line in ["a\n", "b\n", "c\n"] {
+            files
+                .writer_for(Level::INFO)
+                .write_all(line.as_bytes())
+                .unwrap();
+        }
+        Flush::flush(&*files.files, Level::TRACE);
+
+        let mut contents: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| !path.is_symlink())
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect();
+        contents.sort_by_key(|contents| contents.lines().last().map(str::to_owned));
+        assert_eq!(contents.len(), 3);
+        for (contents, line) in contents.iter().zip(["a", "b", "c"]) {
+            assert!(contents.starts_with("Log file created at: "));
+            assert_eq!(contents.matches("Log file created at: ").count(), 1);
+            assert!(contents.ends_with(&format!("msg\n{}\n", line)));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn creation_is_retried_after_an_error() {
+        let parent = temp_dir("missing");
+        let dir = parent.join("missing");
+        let files = LogFiles::new(&dir).with_program_name("yaks");
+        let error = files.writer_for(Level::INFO).write_all(b"a\n").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("could not create the INFO log file"));
+
+        // Until the next attempt, lines are dropped without trying to create the file again.
+        fs::create_dir(&dir).unwrap();
+        files.writer_for(Level::INFO).write_all(b"b\n").unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        thread::sleep(MIN_RETRY_BACKOFF);
+        files.writer_for(Level::WARN).write_all(b"c\n").unwrap();
+        let info = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| !path.is_symlink() && path.to_str().unwrap().contains(".log.INFO."))
+            .unwrap();
+        let info = fs::read_to_string(info).unwrap();
+        assert!(info.ends_with("msg\nc\n"), "{:?}", info);
+        fs::remove_dir_all(&parent).unwrap();
+    }
+
+    #[test]
+    fn retries_back_off() {
+        let parent = temp_dir("backoff");
+        let files = LogFiles::new(parent.join("missing"));
+        let backoff = |files: &LogFiles| match *files.files.slots[0].lock().unwrap() {
+            Slot::Failed { backoff, .. } => backoff,
+            ref slot => panic!("unexpected {:?}", slot),
+        };
+        files.writer_for(Level::INFO).write_all(b"a\n").unwrap_err();
+        assert_eq!(backoff(&files), MIN_RETRY_BACKOFF * 2);
+
+        thread::sleep(MIN_RETRY_BACKOFF);
+        files.writer_for(Level::INFO).write_all(b"b\n").unwrap_err();
+        assert_eq!(backoff(&files), MIN_RETRY_BACKOFF * 4);
+        fs::remove_dir_all(&parent).unwrap();
+    }
+}
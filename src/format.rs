@@ -1,8 +1,30 @@
+use crate::nu_ansi_term::Style;
 #[cfg(feature = "ansi")]
-use crate::nu_ansi_term::{Color, Style};
-use std::{ffi::OsStr, fmt, io, path::Path};
-use time::{format_description::FormatItem, formatting::Formattable, OffsetDateTime};
-use tracing::{Level, Metadata};
+use crate::{
+    links::{FileLinks, Hyperlink},
+    nu_ansi_term::Color,
+    NameColors,
+};
+use crate::{GlogDialect, TimeZone};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::HashMap,
+    env,
+    ffi::OsStr,
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicI32, AtomicU64, AtomicUsize, Ordering},
+        Arc, OnceLock, PoisonError, RwLock,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use time::{
+    format_description::FormatItem, formatting::Formattable, macros::format_description,
+    OffsetDateTime, UtcOffset,
+};
+use tracing::{callsite::Identifier, Level, Metadata};
 use tracing_subscriber::fmt::{format::Writer, time::FormatTime};
 
 /// A bridge between `fmt::Write` and `io::Write`.
@@ -27,11 +49,9 @@ impl<'a> io::Write for WriteAdaptor<'a> {
         let s =
             std::str::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
-        self.fmt_write
-            .write_str(s)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        self.fmt_write.write_str(s).map_err(io::Error::other)?;
 
-        Ok(s.as_bytes().len())
+        Ok(s.len())
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -45,49 +65,393 @@ impl<'a> fmt::Debug for WriteAdaptor<'a> {
     }
 }
 
-pub(crate) struct FmtLevel {
+/// The character and style that [`Glog`] uses for the severity of each level.
+///
+/// By default, levels are written as `T`, `D`, `I`, `W` and `E`, in purple, blue, green,
+/// yellow and red respectively. Styles are only applied when ANSI escapes are enabled, and
+/// can only be set with the `ansi` feature.
+///
+/// # Examples
+///
+/// Write verbose levels as glog's `V`:
+///
+/// ```
+/// use tracing::Level;
+/// use tracing_glog::{Glog, LevelStyle};
+///
+/// let levels = LevelStyle::default()
+///     .with_char(Level::TRACE, 'V')
+///     .with_char(Level::DEBUG, 'V');
+/// let format = Glog::default().with_level_style(levels);
+/// # drop(format);
+/// ```
+///
+/// [`Glog`]: crate::Glog
+#[derive(Clone, Debug)]
+pub struct LevelStyle {
+    /// The characters of `TRACE`, `DEBUG`, `INFO`, `WARN` and `ERROR`, in that order.
+    chars: [char; 5],
+    #[cfg(feature = "ansi")]
+    styles: [Style; 5],
+}
+
+impl LevelStyle {
+    /// Sets the character written for the severity of `level`.
+    pub fn with_char(mut self, level: Level, c: char) -> Self {
+        self.chars[Self::index(level)] = c;
+        self
+    }
+
+    /// Sets the style of the severity character of `level`, including its foreground and
+    /// background colors and whether it is bold.
+    ///
+    /// This method is available with the `ansi` feature.
+    ///
+    /// # Examples
+    ///
+    /// Write `TRACE` in cyan, which is easier to read than purple on a dark background, and
+    /// errors in bold white on red:
+    ///
+    /// ```
+    /// use tracing::Level;
+    /// use tracing_glog::{Color, LevelStyle};
+    ///
+    /// let levels = LevelStyle::default()
+    ///     .with_style(Level::TRACE, Color::Cyan.normal())
+    ///     .with_style(Level::ERROR, Color::White.on(Color::Red).bold());
+    /// # drop(levels);
+    /// ```
+    #[cfg(feature = "ansi")]
+    pub fn with_style(mut self, level: Level, style: Style) -> Self {
+        self.styles[Self::index(level)] = style;
+        self
+    }
+
+    fn index(level: Level) -> usize {
+        match level {
+            Level::TRACE => 0,
+            Level::DEBUG => 1,
+            Level::INFO => 2,
+            Level::WARN => 3,
+            Level::ERROR => 4,
+        }
+    }
+}
+
+impl Default for LevelStyle {
+    fn default() -> Self {
+        LevelStyle {
+            chars: ['T', 'D', 'I', 'W', 'E'],
+            #[cfg(feature = "ansi")]
+            styles: [
+                Color::Purple.normal(),
+                Color::Blue.normal(),
+                Color::Green.normal(),
+                Color::Yellow.normal(),
+                Color::Red.normal(),
+            ],
+        }
+    }
+}
+
+/// The styles of the parts of a line other than its severity, shared by [`Glog`] and
+/// [`GlogFields`].
+///
+/// The default theme only uses text attributes, so that it works with any color scheme: the
+/// timestamp is dimmed, the callsite (thread name, target and `file:line`), span names and
+/// field names are bold, and span fields are italic. `Theme::light` and `Theme::dark` add
+/// colors that are readable on light and dark backgrounds, and `Theme::monochrome_bold`
+/// only uses bold text, for terminals that render neither dimmed nor italic text. Styles are
+/// only applied when ANSI escapes are enabled, and can only be set with the `ansi` feature.
+///
+/// The severity is styled by [`LevelStyle`].
+///
+/// [`Glog`]: crate::Glog
+/// [`GlogFields`]: crate::GlogFields
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(not(feature = "ansi"), allow(dead_code))]
+pub struct Theme {
+    pub(crate) timestamp: Style,
+    pub(crate) callsite: Style,
+    pub(crate) span_name: Style,
+    pub(crate) span_fields: Style,
+    pub(crate) field_name: Style,
+}
+
+#[cfg(feature = "ansi")]
+impl Theme {
+    /// Returns a theme for terminals with a light background.
+    pub fn light() -> Self {
+        Theme {
+            timestamp: Color::DarkGray.normal(),
+            callsite: Color::Black.bold(),
+            span_name: Color::Blue.bold(),
+            span_fields: Color::Blue.normal(),
+            field_name: Color::Purple.normal(),
+        }
+    }
+
+    /// Returns a theme for terminals with a dark background.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_glog::{Glog, GlogFields, Theme};
+    ///
+    /// let theme = Theme::dark();
+    /// tracing_subscriber::fmt()
+    ///     .event_format(Glog::default().with_theme(theme))
+    ///     .fmt_fields(GlogFields::default().with_theme(theme))
+    ///     .init();
+    /// ```
+    pub fn dark() -> Self {
+        Theme {
+            timestamp: Color::DarkGray.normal(),
+            callsite: Color::White.bold(),
+            span_name: Color::LightCyan.bold(),
+            span_fields: Color::Cyan.normal(),
+            field_name: Color::LightBlue.normal(),
+        }
+    }
+
+    /// Returns a theme without colors that only makes the callsite, span names and field
+    /// names bold.
+    pub fn monochrome_bold() -> Self {
+        Theme {
+            timestamp: Style::new(),
+            callsite: Style::new().bold(),
+            span_name: Style::new().bold(),
+            span_fields: Style::new(),
+            field_name: Style::new().bold(),
+        }
+    }
+
+    /// Sets the style of the timestamp, including the one written by
+    /// [`Glog::with_bracketed_timer`].
+    ///
+    /// [`Glog::with_bracketed_timer`]: crate::Glog::with_bracketed_timer
+    pub fn with_timestamp(self, timestamp: Style) -> Self {
+        Theme { timestamp, ..self }
+    }
+
+    /// Sets the style of the callsite: the thread name, the target and `file:line`.
+    pub fn with_callsite(self, callsite: Style) -> Self {
+        Theme { callsite, ..self }
+    }
+
+    /// Sets the style of span names in the span context.
+    pub fn with_span_name(self, span_name: Style) -> Self {
+        Theme { span_name, ..self }
+    }
+
+    /// Sets the style of span fields in the span context.
+    pub fn with_span_fields(self, span_fields: Style) -> Self {
+        Theme {
+            span_fields,
+            ..self
+        }
+    }
+
+    /// Sets the style of the names of event and span fields.
+    pub fn with_field_name(self, field_name: Style) -> Self {
+        Theme { field_name, ..self }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            timestamp: Style::new().dimmed(),
+            callsite: Style::new().bold(),
+            span_name: Style::new().bold(),
+            span_fields: Style::new().italic(),
+            field_name: Style::new().bold(),
+        }
+    }
+}
+
+/// Widths of the thread name, target and `file:line`, which line up the rest of each line in
+/// a column when reading logs in a terminal.
+///
+/// Each column is either [`ColumnWidth::Fixed`] or [`ColumnWidth::Adaptive`], in which case it
+/// widens to fit the widest value written so far. Values wider than a column's width, or
+/// than the maximum width of an adaptive column, are truncated with an ellipsis (`…`): thread
+/// names lose their end, while targets and paths lose their start, which is the least
+/// specific part. Widths are counted in characters; styling and hyperlinks take no room.
+/// Columns that are not set are neither padded nor truncated, which is the default.
+///
+/// Use [`Glog::with_columns`] to align lines.
+///
+/// # Compatibility with glog parsers
+///
+/// Aligned lines remain parsable by tools that split the glog prefix on whitespace:
+///
+/// - padding is only ever made of spaces, written between the tokens of the prefix: after
+///   the thread name, after the closing bracket of the target, and before `file:line`;
+/// - `file:line]` is always written in one piece, with the line number in full, so the
+///   prefix still ends at the first `] ` after the process id, and the message starts right
+///   after it, without padding.
+///
+/// Truncated paths are no longer the paths of actual files, so leave the location column
+/// unset if a tool needs to find the source files.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_glog::{ColumnWidth, Columns, Glog, GlogFields};
+///
+/// let columns = Columns::default()
+///     .with_thread_name(ColumnWidth::Fixed(12))
+///     .with_target(ColumnWidth::Adaptive { max: 32 })
+///     .with_location(ColumnWidth::Adaptive { max: 24 });
+/// tracing_subscriber::fmt()
+///     .event_format(
+///         Glog::default()
+///             .with_thread_names(true)
+///             .with_target(true)
+///             .with_columns(columns),
+///     )
+///     .fmt_fields(GlogFields::default())
+///     .init();
+/// ```
+///
+/// [`Glog::with_columns`]: crate::Glog::with_columns
+#[derive(Clone, Debug, Default)]
+pub struct Columns {
+    pub(crate) thread_name: Column,
+    pub(crate) target: Column,
+    pub(crate) location: Column,
+}
+
+/// The width of one of the [`Columns`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnWidth {
+    /// Pads values to this many characters, and truncates wider ones.
+    Fixed(usize),
+    /// Pads values to the width of the widest value written so far, and truncates values
+    /// wider than `max` characters. Lines only start to line up once the widest values have
+    /// been written.
+    Adaptive {
+        /// The width that the column does not grow past.
+        max: usize,
+    },
+}
+
+impl Columns {
+    /// Sets the width of the thread name, when thread names are written (see
+    /// [`Glog::with_thread_names`]).
+    ///
+    /// [`Glog::with_thread_names`]: crate::Glog::with_thread_names
+    pub fn with_thread_name(self, width: ColumnWidth) -> Self {
+        Columns {
+            thread_name: Column::new(width),
+            ..self
+        }
+    }
+
+    /// Sets the width of the target, without its brackets, when targets are written (see
+    /// [`Glog::with_target`]). Targets are truncated after being abbreviated (see
+    /// [`Glog::with_abbreviated_target`]).
+    ///
+    /// [`Glog::with_target`]: crate::Glog::with_target
+    /// [`Glog::with_abbreviated_target`]: crate::Glog::with_abbreviated_target
+    pub fn with_target(self, width: ColumnWidth) -> Self {
+        Columns {
+            target: Column::new(width),
+            ..self
+        }
+    }
+
+    /// Sets the width of `file:line`. Only the path is truncated, never the line number.
+    pub fn with_location(self, width: ColumnWidth) -> Self {
+        Columns {
+            location: Column::new(width),
+            ..self
+        }
+    }
+}
+
+/// One of the [`Columns`], with the width of the widest value written to it.
+#[derive(Debug, Default)]
+pub(crate) struct Column {
+    width: Option<ColumnWidth>,
+    widest: AtomicUsize,
+}
+
+impl Column {
+    fn new(width: ColumnWidth) -> Self {
+        Column {
+            width: Some(width),
+            widest: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.width.is_some()
+    }
+
+    /// Returns the width past which values are truncated.
+    pub(crate) fn limit(&self) -> Option<usize> {
+        match self.width? {
+            ColumnWidth::Fixed(width) => Some(width),
+            ColumnWidth::Adaptive { max } => Some(max),
+        }
+    }
+
+    /// Returns the number of spaces that pad a value of the given width, which is already
+    /// truncated.
+    pub(crate) fn padding(&self, width: usize) -> usize {
+        match self.width {
+            None => 0,
+            Some(ColumnWidth::Fixed(column)) => column.saturating_sub(width),
+            Some(ColumnWidth::Adaptive { max }) => {
+                let width = width.min(max);
+                let widest = self.widest.fetch_max(width, Ordering::Relaxed).max(width);
+                widest - width
+            }
+        }
+    }
+}
+
+impl Clone for Column {
+    fn clone(&self) -> Self {
+        Column {
+            width: self.width,
+            widest: AtomicUsize::new(self.widest.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+pub(crate) struct FmtLevel<'a> {
     pub level: Level,
+    pub style: &'a LevelStyle,
     #[cfg(feature = "ansi")]
     pub ansi: bool,
 }
 
-impl FmtLevel {
-    const TRACE_STR: &'static str = "T";
-    const DEBUG_STR: &'static str = "D";
-    const INFO_STR: &'static str = "I";
-    const WARN_STR: &'static str = "W";
-    const ERROR_STR: &'static str = "E";
-
-    pub(crate) fn format_level(level: Level, ansi: bool) -> FmtLevel {
+impl<'a> FmtLevel<'a> {
+    pub(crate) fn format_level(level: Level, style: &'a LevelStyle, ansi: bool) -> FmtLevel<'a> {
         #[cfg(not(feature = "ansi"))]
         let _ = ansi;
         FmtLevel {
             level,
+            style,
             #[cfg(feature = "ansi")]
             ansi,
         }
     }
 }
 
-impl fmt::Display for FmtLevel {
+impl<'a> fmt::Display for FmtLevel<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let index = LevelStyle::index(self.level);
+        let mut buf = [0; 4];
+        let c = self.style.chars[index].encode_utf8(&mut buf);
         #[cfg(feature = "ansi")]
         if self.ansi {
-            return match self.level {
-                Level::TRACE => write!(f, "{}", Color::Purple.paint(Self::TRACE_STR)),
-                Level::DEBUG => write!(f, "{}", Color::Blue.paint(Self::DEBUG_STR)),
-                Level::INFO => write!(f, "{}", Color::Green.paint(Self::INFO_STR)),
-                Level::WARN => write!(f, "{}", Color::Yellow.paint(Self::WARN_STR)),
-                Level::ERROR => write!(f, "{}", Color::Red.paint(Self::ERROR_STR)),
-            };
-        }
-        match self.level {
-            Level::TRACE => f.pad(Self::TRACE_STR),
-            Level::DEBUG => f.pad(Self::DEBUG_STR),
-            Level::INFO => f.pad(Self::INFO_STR),
-            Level::WARN => f.pad(Self::WARN_STR),
-            Level::ERROR => f.pad(Self::ERROR_STR),
+            return write!(f, "{}", self.style.styles[index].paint(&*c));
         }
+        f.pad(c)
     }
 }
 
@@ -104,33 +468,180 @@ pub struct UtcTime<F = Vec<FormatItem<'static>>> {
     format: F,
 }
 
-impl<F> FormatTime for UtcTime<F>
+impl<F> UtcTime<F>
 where
     F: Formattable,
 {
-    fn format_time(&self, writer: &mut Writer<'_>) -> fmt::Result {
-        let now = OffsetDateTime::now_utc();
+    /// Returns a timer that formats the current [UTC time] using the provided [formatter].
+    ///
+    /// [`Glog`] writes the timestamp in the style of its [`Theme`]; the timer itself never
+    /// writes ANSI escapes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use time::macros::format_description;
+    /// use tracing_glog::{Glog, UtcTime};
+    ///
+    /// let timer = UtcTime::new(format_description!(
+    ///     "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:3]"
+    /// ));
+    /// let format = Glog::default().with_timer(timer);
+    /// # drop(format);
+    /// ```
+    ///
+    /// [UTC time]: time::OffsetDateTime::now_utc
+    /// [formatter]: time::formatting::Formattable
+    /// [`Glog`]: crate::Glog
+    pub fn new(format: F) -> Self {
+        Self { format }
+    }
+}
 
-        #[cfg(feature = "ansi")]
-        if writer.has_ansi_escapes() {
-            let style = Style::new().dimmed();
-            write!(writer, "{}", style.prefix())?;
-            format_datetime(writer, now, &self.format)?;
-            write!(writer, "{}", style.suffix())?;
-            return Ok(());
-        }
+impl UtcTime {
+    /// Returns a timer that includes the year in the date, as glog does with
+    /// `--log_year_in_prefix`: `yyyymmdd hh:mm:ss.uuuuuu`.
+    pub fn with_year() -> Self {
+        Self::new(GlogDialect::GlogWithYear.time_format())
+    }
 
-        format_datetime(writer, now, &self.format)
+    /// Returns a timer that records nanoseconds rather than microseconds:
+    /// `mmdd hh:mm:ss.nnnnnnnnn`.
+    pub fn nanos() -> Self {
+        Self::new(nanos_format())
+    }
+}
+
+impl<F> FormatTime for UtcTime<F>
+where
+    F: Formattable,
+{
+    fn format_time(&self, writer: &mut Writer<'_>) -> fmt::Result {
+        format_datetime(writer, OffsetDateTime::now_utc(), &self.format)
     }
 }
 
 impl Default for UtcTime {
     fn default() -> Self {
-        let format: Vec<FormatItem> = time::format_description::parse(
-            "[month][day] [hour]:[minute]:[second].[subsecond digits:6]",
+        GlogDialect::Glog.utc_time()
+    }
+}
+
+/// Formats the current [UTC time] in glog's `mmdd hh:mm:ss.uuuuuu` format, rendering the
+/// date and the time of day at most once per second per thread.
+///
+/// [`UtcTime`] runs its whole format description for every event. For high-throughput
+/// services, `CachedUtcTime` instead keeps the `mmdd hh:mm:ss` part of the most recent
+/// timestamp in a thread-local buffer and only renders the microseconds afresh, producing
+/// byte-for-byte the same output as `UtcTime::default()`. Run `cargo bench --bench timer` to
+/// compare the two.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_glog::{CachedUtcTime, Glog, GlogFields};
+///
+/// tracing_subscriber::fmt()
+///     .event_format(Glog::default().with_timer(CachedUtcTime::new()))
+///     .fmt_fields(GlogFields::default())
+///     .init();
+/// ```
+///
+/// [UTC time]: time::OffsetDateTime::now_utc
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CachedUtcTime {
+    with_year: bool,
+}
+
+impl CachedUtcTime {
+    /// Returns a timer in glog's default `mmdd hh:mm:ss.uuuuuu` format.
+    pub fn new() -> Self {
+        CachedUtcTime { with_year: false }
+    }
+
+    /// Returns a timer that includes the year in the date: `yyyymmdd hh:mm:ss.uuuuuu`.
+    pub fn with_year() -> Self {
+        CachedUtcTime { with_year: true }
+    }
+
+    /// Writes the time `since_epoch` after the Unix epoch, reusing this thread's rendering
+    /// of its second if it has one.
+    fn format_at(&self, since_epoch: Duration, writer: &mut Writer<'_>) -> fmt::Result {
+        let second = since_epoch.as_secs() as i64;
+
+        // `.uuuuuu`, rendered without going through `fmt` machinery.
+        let mut micros = *b".000000";
+        let mut n = since_epoch.subsec_micros();
+        for digit in micros[1..].iter_mut().rev() {
+            *digit = b'0' + (n % 10) as u8;
+            n /= 10;
+        }
+        let micros = std::str::from_utf8(&micros).expect("digits are valid UTF-8");
+
+        let cached = CACHED_SECOND.try_with(|cached| {
+            let mut cached = cached.try_borrow_mut().map_err(|_| fmt::Error)?;
+            if cached.second != second || cached.with_year != self.with_year {
+                cached.rendered.clear();
+                self.render_second(second, &mut cached.rendered)?;
+                cached.second = second;
+                cached.with_year = self.with_year;
+            }
+            writer.write_str(&cached.rendered)
+        });
+        match cached {
+            Ok(result) => result?,
+            // The thread-local is being torn down; render this second without caching it.
+            Err(_) => {
+                let mut rendered = String::new();
+                self.render_second(second, &mut rendered)?;
+                writer.write_str(&rendered)?;
+            }
+        }
+        writer.write_str(micros)
+    }
+
+    fn render_second(&self, second: i64, into: &mut String) -> fmt::Result {
+        use fmt::Write;
+
+        let now = OffsetDateTime::from_unix_timestamp(second).map_err(|_| fmt::Error)?;
+        if self.with_year {
+            write!(into, "{:04}", now.year())?;
+        }
+        write!(
+            into,
+            "{:02}{:02} {:02}:{:02}:{:02}",
+            now.month() as u8,
+            now.day(),
+            now.hour(),
+            now.minute(),
+            now.second()
         )
-        .expect("Unable to make time formatter");
-        Self { format }
+    }
+}
+
+/// The most recently rendered second, as `mmdd hh:mm:ss` or `yyyymmdd hh:mm:ss`.
+struct CachedSecond {
+    second: i64,
+    with_year: bool,
+    rendered: String,
+}
+
+thread_local! {
+    static CACHED_SECOND: RefCell<CachedSecond> = const {
+        RefCell::new(CachedSecond {
+            second: i64::MIN,
+            with_year: false,
+            rendered: String::new(),
+        })
+    };
+}
+
+impl FormatTime for CachedUtcTime {
+    fn format_time(&self, writer: &mut Writer<'_>) -> fmt::Result {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.format_at(now, writer)
     }
 }
 
@@ -138,17 +649,28 @@ impl Default for UtcTime {
 ///
 /// To format the current [UTC time] instead, use the [`UtcTime`] type.
 ///
+/// The local time zone is read from the `TZ` environment variable or, if it is not set, from
+/// `/etc/localtime`, which is sound in multi-threaded processes and follows daylight saving
+/// time transitions. If neither can be read, as on Windows, the offset is looked up with the
+/// [`time` crate] instead.
+///
 /// <div class="example-wrap" style="display:inline-block">
 /// <pre class="compile_fail" style="white-space:normal;font:inherit;">
 ///     <strong>Warning</strong>: The <a href = "https://docs.rs/time/0.3/time/"><code>time</code>
-///     crate</a> must be compiled with <code>--cfg unsound_local_offset</code> in order to use
-///     local timestamps. When this cfg is not enabled, local timestamps cannot be recorded, and
-///     events will be logged without timestamps.
+///     crate</a> must be compiled with <code>--cfg unsound_local_offset</code> in order to
+///     look up the local offset once the process has started other threads. When this cfg is
+///     not enabled and the time zone cannot be read, use
+///     <code>LocalTime::from_startup_offset</code> to capture the offset during
+///     initialization instead.
 ///
 ///    See the <a href="https://docs.rs/time/0.3.4/time/#feature-flags"><code>time</code>
 ///    documentation</a> for more details.
 /// </pre></div>
 ///
+/// If the local offset cannot be determined, the timestamp is written in UTC rather than
+/// dropping the event, so that the line keeps glog's layout. To tell such timestamps apart,
+/// include the offset in the format, for instance with `[offset_hour sign:mandatory]`.
+///
 /// [local time]: time::OffsetDateTime::now_local
 /// [formatter]: time::formatting::Formattable
 /// [`time` crate]: time
@@ -156,40 +678,227 @@ impl Default for UtcTime {
 #[derive(Clone, Debug)]
 pub struct LocalTime<F = Vec<FormatItem<'static>>> {
     format: F,
+    offset: LocalOffset,
 }
 
-impl Default for LocalTime {
-    fn default() -> Self {
-        let format: Vec<FormatItem> = time::format_description::parse(
-            "[month][day] [hour]:[minute]:[second].[subsecond digits:6]",
-        )
-        .expect("Unable to make time formatter");
-        Self { format }
+/// How a [`LocalTime`] determines the local offset from UTC.
+#[derive(Clone, Debug)]
+enum LocalOffset {
+    /// Look up the offset for every event, in the local time zone loaded by the first event.
+    Current(Arc<OnceLock<Option<TimeZone>>>),
+    /// Use an offset captured ahead of time, optionally re-checking it periodically.
+    Captured(Arc<CapturedOffset>),
+}
+
+#[derive(Debug)]
+struct CapturedOffset {
+    /// The offset in seconds, or [`CapturedOffset::UNKNOWN`].
+    seconds: AtomicI32,
+    refresh: Option<Duration>,
+    /// Looks up the offset at the given instant.
+    lookup: fn(OffsetDateTime) -> Option<UtcOffset>,
+    /// Milliseconds since `start` after which the offset should be re-checked.
+    next_refresh: AtomicU64,
+    start: Instant,
+}
+
+impl CapturedOffset {
+    const UNKNOWN: i32 = i32::MIN;
+
+    fn capture(refresh: Option<Duration>) -> Self {
+        Self::capture_with(local_offset_at, refresh)
+    }
+
+    fn capture_with(
+        lookup: fn(OffsetDateTime) -> Option<UtcOffset>,
+        refresh: Option<Duration>,
+    ) -> Self {
+        let seconds =
+            lookup(OffsetDateTime::now_utc()).map_or(Self::UNKNOWN, UtcOffset::whole_seconds);
+        CapturedOffset {
+            seconds: AtomicI32::new(seconds),
+            refresh,
+            lookup,
+            next_refresh: AtomicU64::new(refresh.map_or(u64::MAX, |r| r.as_millis() as u64)),
+            start: Instant::now(),
+        }
+    }
+
+    fn offset_at(&self, now: OffsetDateTime) -> Option<UtcOffset> {
+        if let Some(refresh) = self.refresh {
+            let elapsed = self.start.elapsed().as_millis() as u64;
+            let next = self.next_refresh.load(Ordering::Relaxed);
+            // Only one thread gets to re-check the offset once it is due.
+            if elapsed >= next
+                && self
+                    .next_refresh
+                    .compare_exchange(
+                        next,
+                        elapsed + refresh.as_millis() as u64,
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    )
+                    .is_ok()
+            {
+                // Keep the previous offset if the time zone cannot be read any more.
+                if let Some(offset) = (self.lookup)(now) {
+                    self.seconds
+                        .store(offset.whole_seconds(), Ordering::Relaxed);
+                }
+            }
+        }
+
+        match self.seconds.load(Ordering::Relaxed) {
+            Self::UNKNOWN => None,
+            seconds => UtcOffset::from_whole_seconds(seconds).ok(),
+        }
     }
 }
 
-impl<F> FormatTime for LocalTime<F>
+/// Returns the local offset from UTC at `now`, reading the local time zone afresh.
+fn local_offset_at(now: OffsetDateTime) -> Option<UtcOffset> {
+    match TimeZone::local() {
+        Ok(zone) => Some(zone.offset_at(now)),
+        // This fails on multi-threaded Unix processes unless `time` is built with
+        // `unsound_local_offset`.
+        Err(_) => UtcOffset::local_offset_at(now).ok(),
+    }
+}
+
+impl<F> LocalTime<F>
 where
     F: Formattable,
 {
-    fn format_time(&self, writer: &mut Writer<'_>) -> fmt::Result {
-        let now = OffsetDateTime::now_local().map_err(|_| fmt::Error)?;
+    /// Returns a timer that formats the current [local time] using the provided [formatter].
+    ///
+    /// [`Glog`] writes the timestamp in the style of its [`Theme`]; the timer itself never
+    /// writes ANSI escapes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use time::macros::format_description;
+    /// use tracing_glog::{Glog, LocalTime};
+    ///
+    /// let timer = LocalTime::new(format_description!(
+    ///     "[month][day] [hour]:[minute]:[second].[subsecond digits:6] [offset_hour sign:mandatory]"
+    /// ));
+    /// let format = Glog::default().with_timer(timer);
+    /// # drop(format);
+    /// ```
+    ///
+    /// [local time]: time::OffsetDateTime::now_local
+    /// [formatter]: time::formatting::Formattable
+    /// [`Glog`]: crate::Glog
+    pub fn new(format: F) -> Self {
+        Self {
+            format,
+            offset: LocalOffset::Current(Arc::default()),
+        }
+    }
 
-        #[cfg(feature = "ansi")]
-        if writer.has_ansi_escapes() {
-            let style = Style::new().dimmed();
-            write!(writer, "{}", style.prefix())?;
-            format_datetime(writer, now, &self.format)?;
-            // necessary to provide space between the time and the PID
-            write!(writer, "{}", style.suffix())?;
-            return Ok(());
+    /// Captures the local offset from UTC now and uses it for every subsequent event.
+    ///
+    /// Where the local time zone cannot be read, looking up the local offset is only sound
+    /// while the process is single-threaded, so this should be called from `main` before any
+    /// other threads (such as a Tokio runtime) are started. If the offset cannot be
+    /// determined, timestamps are written in UTC.
+    ///
+    /// The captured offset does not follow daylight saving time transitions; see
+    /// [`LocalTime::with_offset_refresh`].
+    pub fn with_startup_offset(self) -> Self {
+        Self {
+            offset: LocalOffset::Captured(Arc::new(CapturedOffset::capture(None))),
+            ..self
+        }
+    }
+
+    /// Captures the local offset from UTC now, and re-checks it every `interval` so that
+    /// daylight saving time transitions are picked up.
+    ///
+    /// The offset is re-checked by reading the local time zone again, as described for
+    /// [`LocalTime`], so changes to `/etc/localtime` are picked up too. If that fails, the
+    /// last known offset continues to be used.
+    pub fn with_offset_refresh(self, interval: Duration) -> Self {
+        Self {
+            offset: LocalOffset::Captured(Arc::new(CapturedOffset::capture(Some(interval)))),
+            ..self
         }
+    }
+}
+
+impl LocalTime {
+    /// Returns a timer in the default glog format that uses the local offset from UTC at the
+    /// time it was created.
+    ///
+    /// This should be called from `main` before other threads are started. See
+    /// [`LocalTime::with_startup_offset`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_glog::{Glog, GlogFields, LocalTime};
+    ///
+    /// tracing_subscriber::fmt()
+    ///     .event_format(Glog::default().with_timer(LocalTime::from_startup_offset()))
+    ///     .fmt_fields(GlogFields::default())
+    ///     .init();
+    /// ```
+    pub fn from_startup_offset() -> Self {
+        Self::default().with_startup_offset()
+    }
 
+    /// Returns a timer that includes the year in the date, as glog does with
+    /// `--log_year_in_prefix`: `yyyymmdd hh:mm:ss.uuuuuu`.
+    pub fn with_year() -> Self {
+        Self::new(GlogDialect::GlogWithYear.time_format())
+    }
+
+    /// Returns a timer that records nanoseconds rather than microseconds:
+    /// `mmdd hh:mm:ss.nnnnnnnnn`.
+    pub fn nanos() -> Self {
+        Self::new(nanos_format())
+    }
+}
+
+impl<F> LocalTime<F>
+where
+    F: Formattable,
+{
+    fn format_at(&self, utc: OffsetDateTime, writer: &mut Writer<'_>) -> fmt::Result {
+        let offset = match &self.offset {
+            LocalOffset::Current(zone) => match zone.get_or_init(|| TimeZone::local().ok()) {
+                Some(zone) => Some(zone.offset_at(utc)),
+                None => UtcOffset::local_offset_at(utc).ok(),
+            },
+            LocalOffset::Captured(captured) => captured.offset_at(utc),
+        };
+        // Fall back to UTC rather than dropping the event.
+        let now = offset.map_or(utc, |offset| utc.to_offset(offset));
         format_datetime(writer, now, &self.format)
     }
 }
 
-fn format_datetime(
+impl Default for LocalTime {
+    fn default() -> Self {
+        GlogDialect::Glog.local_time()
+    }
+}
+
+impl<F> FormatTime for LocalTime<F>
+where
+    F: Formattable,
+{
+    fn format_time(&self, writer: &mut Writer<'_>) -> fmt::Result {
+        self.format_at(OffsetDateTime::now_utc(), writer)
+    }
+}
+
+fn nanos_format() -> Vec<FormatItem<'static>> {
+    format_description!("[month][day] [hour]:[minute]:[second].[subsecond digits:9]").to_vec()
+}
+
+pub(crate) fn format_datetime(
     into: &mut Writer<'_>,
     now: OffsetDateTime,
     fmt: &impl Formattable,
@@ -204,18 +913,213 @@ pub(crate) struct FormatProcessData<'a> {
     pub(crate) pid: u32,
     pub(crate) thread_name: Option<&'a str>,
     pub(crate) with_thread_names: bool,
-    pub(crate) metadata: &'a Metadata<'a>,
     pub(crate) with_target: bool,
+    pub(crate) callsite: &'a CallsitePrefix,
+    pub(crate) columns: &'a Columns,
     #[cfg(feature = "ansi")]
     pub(crate) ansi: bool,
-    pub(crate) with_trimmed_directory: bool,
-    pub(crate) with_strip_prefix: &'a Option<String>,
+    #[cfg(feature = "ansi")]
+    pub(crate) style: Style,
+    pub(crate) pid_width: usize,
 }
 
 impl<'a> fmt::Display for FormatProcessData<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let thread_name = self.thread_name;
+        // write the always unstyled PID
+        write!(f, " {pid:>width$}", pid = self.pid, width = self.pid_width)?;
+
+        #[cfg(feature = "ansi")]
+        let ansi = self.ansi;
+        #[cfg(not(feature = "ansi"))]
+        let ansi = false;
+
+        // style the thread name and the callsite together
+        #[cfg(feature = "ansi")]
+        if ansi {
+            write!(f, "{}", self.style.prefix())?;
+        }
+
+        // Unnamed threads leave their column blank, so that the columns after it line up.
+        let column = &self.columns.thread_name;
+        let thread_name = match self.thread_name {
+            Some(name) => Some(truncate_end(name, column.limit())),
+            None if column.is_enabled() => Some("".into()),
+            None => None,
+        };
+        if let Some(name) = thread_name.filter(|_| self.with_thread_names) {
+            let padding = column.padding(name.chars().count());
+            write!(f, " {}{:padding$}", name, "")?;
+        }
+
+        let callsite = self.callsite;
+        if self.with_target {
+            let target = &callsite.target;
+            let padding = self.columns.target.padding(target.width);
+            write!(f, " [{}]{:padding$}", target.get(ansi), "")?;
+        }
+        // `file:line` is right-aligned, so that the padding stays outside of the `file:line]`
+        // that parsers look for.
+        let location = &callsite.location;
+        let padding = self.columns.location.padding(location.width);
+        write!(f, " {:padding$}{}", "", location.get(ansi))?;
+
+        #[cfg(feature = "ansi")]
+        if ansi {
+            write!(f, "{}", self.style.suffix())?;
+        }
+        Ok(())
+    }
+}
+
+/// The part of the glog prefix that never changes for a callsite: the target and `file:line`.
+/// The target is rendered even if it is not written, so that the same prefix serves whether
+/// or not [`GlogHandle::set_target`] has turned it on.
+///
+/// This is rendered once per callsite and cached by [`CallsiteCache`], so that the per-event
+/// cost of the prefix is only the timestamp and the thread information. Both are truncated
+/// to the limits of their [`Columns`], but not padded, since adaptive columns widen as events
+/// are written.
+///
+/// [`GlogHandle::set_target`]: crate::GlogHandle::set_target
+pub(crate) struct CallsitePrefix {
+    /// The target, without its brackets.
+    target: CallsiteToken,
+    location: CallsiteToken,
+}
+
+impl CallsitePrefix {
+    pub(crate) fn new(format: &FormatCallsite<'_>) -> Self {
+        CallsitePrefix {
+            target: format.target_token(),
+            location: format.location_token(),
+        }
+    }
+}
+
+/// A part of a [`CallsitePrefix`].
+struct CallsiteToken {
+    plain: Box<str>,
+    /// The variant with a colored target or a linked `file:line`, which is written in the
+    /// callsite style.
+    #[cfg(feature = "ansi")]
+    styled: Option<Box<str>>,
+    /// The width of the plain variant, in characters, which is also the visible width of the
+    /// styled one.
+    width: usize,
+}
+
+impl CallsiteToken {
+    fn new(plain: String) -> Self {
+        CallsiteToken {
+            width: plain.chars().count(),
+            plain: plain.into_boxed_str(),
+            #[cfg(feature = "ansi")]
+            styled: None,
+        }
+    }
+
+    #[cfg(feature = "ansi")]
+    fn with_styled(self, styled: Option<String>) -> Self {
+        CallsiteToken {
+            styled: styled.map(String::into_boxed_str),
+            ..self
+        }
+    }
+
+    fn get(&self, ansi: bool) -> &str {
+        #[cfg(feature = "ansi")]
+        if ansi {
+            if let Some(styled) = &self.styled {
+                return styled;
+            }
+        }
+        #[cfg(not(feature = "ansi"))]
+        let _ = ansi;
+        &self.plain
+    }
+}
+
+/// The directories that [`Glog::with_cargo_paths`] removes from paths.
+///
+/// [`Glog::with_cargo_paths`]: crate::Glog::with_cargo_paths
+#[derive(Debug)]
+pub(crate) struct CargoPaths {
+    /// `$CARGO_HOME/registry/src`, which holds a directory for each registry.
+    registry_src: Option<PathBuf>,
+    workspace_root: Option<PathBuf>,
+}
+
+impl CargoPaths {
+    pub(crate) fn detect() -> Self {
+        let cargo_home = env::var_os("CARGO_HOME")
+            .filter(|home| !home.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                ["HOME", "USERPROFILE"]
+                    .into_iter()
+                    .find_map(env::var_os)
+                    .map(|home| Path::new(&home).join(".cargo"))
+            });
+        let start = env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .or_else(|| env::current_dir().ok());
+        CargoPaths {
+            registry_src: cargo_home.map(|home| home.join("registry").join("src")),
+            workspace_root: start.and_then(|start| {
+                start
+                    .ancestors()
+                    .find(|dir| dir.join("Cargo.lock").is_file())
+                    .map(Path::to_path_buf)
+            }),
+        }
+    }
+
+    fn strip<'a>(&self, file: &'a str) -> Option<&'a str> {
+        let path = Path::new(file);
+        if let Some(in_registry) = self
+            .registry_src
+            .as_ref()
+            .and_then(|registry_src| path.strip_prefix(registry_src).ok())
+        {
+            // Skip the registry's directory, such as `index.crates.io-6f17d22bba15001f`.
+            let mut components = in_registry.components();
+            components.next();
+            return components.as_path().to_str();
+        }
+        let workspace_root = self.workspace_root.as_ref()?;
+        path.strip_prefix(workspace_root).ok()?.to_str()
+    }
+}
+
+/// Renders the target and `file:line` of a callsite.
+pub(crate) struct FormatCallsite<'a> {
+    pub(crate) metadata: &'a Metadata<'a>,
+    pub(crate) with_trimmed_directory: bool,
+    pub(crate) strip_prefixes: &'a [String],
+    pub(crate) cargo_paths: Option<&'a CargoPaths>,
+    /// The maximum widths of the target and of the file's path; see [`abbreviate`].
+    pub(crate) target_abbreviation: Option<usize>,
+    pub(crate) file_abbreviation: Option<usize>,
+    pub(crate) columns: &'a Columns,
+    /// The colors of targets, and the callsite style that the target is written in.
+    #[cfg(feature = "ansi")]
+    pub(crate) target_colors: Option<(&'a NameColors, Style)>,
+    #[cfg(feature = "ansi")]
+    pub(crate) file_links: Option<&'a FileLinks>,
+}
+
+impl<'a> FormatCallsite<'a> {
+    /// Returns the target, abbreviated as configured.
+    fn target(&self) -> Cow<'a, str> {
         let target = self.metadata.target();
+        match self.target_abbreviation {
+            Some(max_width) => abbreviate(target, |c| c == ':', max_width),
+            None => target.into(),
+        }
+    }
+
+    /// Returns the file's path, trimmed, stripped and abbreviated as configured.
+    fn file(&self) -> Cow<'a, str> {
         let file = self
             .metadata
             .file()
@@ -226,66 +1130,195 @@ impl<'a> fmt::Display for FormatProcessData<'a> {
                         .map(OsStr::to_str)
                         .unwrap_or(Some(f))
                         .unwrap_or(f)
-                } else if let Some(prefix) = &self.with_strip_prefix {
-                    Path::new(f)
-                        .strip_prefix(prefix)
-                        .map(|path| path.to_str().unwrap())
-                        .unwrap_or(f)
                 } else {
-                    f
+                    self.strip_prefixes
+                        .iter()
+                        .find_map(|prefix| Path::new(f).strip_prefix(prefix).ok())
+                        .and_then(Path::to_str)
+                        .or_else(|| self.cargo_paths?.strip(f))
+                        .unwrap_or(f)
                 }
             })
             .unwrap_or("");
-        let line = match self.metadata.line() {
-            Some(line) => format!("{}", line),
-            None => String::new(),
-        };
-        // write the always unstyled PID
-        write!(f, " {pid:>5}", pid = self.pid)?;
+        match self.file_abbreviation {
+            Some(max_width) => abbreviate(file, |c| c == '/' || c == '\\', max_width),
+            None => file.into(),
+        }
+    }
 
+    /// Renders the target, colored if that is enabled.
+    fn target_token(&self) -> CallsiteToken {
+        let target = truncate_start(self.target(), self.columns.target.limit());
+        let token = CallsiteToken::new(target.to_string());
+        // The colored target ends all styling, so the callsite style is restored after it. Its
+        // color is picked from the full target, so that it does not depend on the
+        // abbreviation.
         #[cfg(feature = "ansi")]
-        if self.ansi {
-            let style = Style::new().bold();
-            // start by bolding all the expected data
-            write!(f, "{}", style.prefix())?;
-            if let Some(name) = thread_name {
-                if self.with_thread_names {
-                    write!(f, " {}", name)?
+        let token = token.with_styled(self.target_colors.map(|(colors, style)| {
+            let color = colors.color_for(self.metadata.target());
+            format!("{}{}", style.fg(color).paint(target), style.prefix())
+        }));
+        token
+    }
+
+    /// Renders `file:line`, linked if that is enabled. The path is truncated to fit the
+    /// location column, but the line number is always written in full.
+    fn location_token(&self) -> CallsiteToken {
+        let line = self.metadata.line();
+        let file = self.columns.location.limit().map_or_else(
+            || self.file(),
+            |limit| {
+                let line_width = line.map_or(0, |line| line.to_string().len());
+                truncate_start(self.file(), Some(limit.saturating_sub(line_width + 1)))
+            },
+        );
+        let location = FormatLocation { file: &file, line };
+        let token = CallsiteToken::new(location.to_string());
+        #[cfg(feature = "ansi")]
+        let token = token.with_styled(self.file_links.zip(self.metadata.file()).map(
+            |(links, path)| {
+                let url = links.url(path, line);
+                Hyperlink {
+                    url: &url,
+                    text: location,
                 }
-            }
+                .to_string()
+            },
+        ));
+        token
+    }
+}
 
-            if self.with_target {
-                write!(f, " [{}]", target)?;
-            }
+/// Renders `file:line`.
+struct FormatLocation<'a> {
+    file: &'a str,
+    line: Option<u32>,
+}
 
-            write!(f, " {file}:{line}", file = file, line = line)?;
+impl<'a> fmt::Display for FormatLocation<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
 
-            // end bolding
-            write!(f, "{}", style.suffix())?;
+/// The character that replaces the part of a value that does not fit in its column.
+const ELLIPSIS: char = '…';
 
-            return Ok(());
+/// Shortens `value` to `limit` characters, if it is wider, by replacing its start with an
+/// ellipsis.
+fn truncate_start(value: Cow<'_, str>, limit: Option<usize>) -> Cow<'_, str> {
+    let width = value.chars().count();
+    match limit {
+        Some(limit) if width > limit => {
+            let skipped = width - limit.saturating_sub(1);
+            std::iter::once(ELLIPSIS)
+                .chain(value.chars().skip(skipped))
+                .collect::<String>()
+                .into()
         }
-        if let Some(name) = thread_name {
-            if self.with_thread_names {
-                write!(f, " {}", name)?
+        _ => value,
+    }
+}
+
+/// Shortens `value` to `limit` characters, if it is wider, by replacing its end with an
+/// ellipsis.
+fn truncate_end(value: &str, limit: Option<usize>) -> Cow<'_, str> {
+    match limit {
+        Some(limit) if value.chars().count() > limit => value
+            .chars()
+            .take(limit.saturating_sub(1))
+            .chain(std::iter::once(ELLIPSIS))
+            .collect::<String>()
+            .into(),
+        _ => value.into(),
+    }
+}
+
+/// Abbreviates `name` to at most `max_width` characters, if possible, by shortening its
+/// leading segments to their first character, from left to right, like Logback's logger
+/// names. The last segment is always kept in full, so the result may be wider than
+/// `max_width`.
+///
+/// Segments are separated by runs of characters for which `is_separator` is true, so that
+/// `my_company::storage::backend::s3::client` becomes `m::s::b::s3::client` when abbreviated
+/// to 20 characters, and `src/runtime/scheduler/mod.rs` becomes `s/r/scheduler/mod.rs`.
+fn abbreviate(name: &str, is_separator: fn(char) -> bool, max_width: usize) -> Cow<'_, str> {
+    let mut width = name.chars().count();
+    if width <= max_width {
+        return name.into();
+    }
+
+    let pieces: Vec<&str> = name.split_inclusive(is_separator).collect();
+    let Some((last, leading)) = pieces.split_last() else {
+        return name.into();
+    };
+    let mut abbreviated = String::with_capacity(name.len());
+    for piece in leading {
+        let segment = piece.trim_end_matches(is_separator);
+        let separator = &piece[segment.len()..];
+        match segment.chars().next() {
+            Some(first) if width > max_width => {
+                width -= segment.chars().count() - 1;
+                abbreviated.push(first);
+                abbreviated.push_str(separator);
             }
+            _ => abbreviated.push_str(piece),
         }
+    }
+    abbreviated.push_str(last);
+    abbreviated.into()
+}
 
-        if self.with_target {
-            write!(f, " [{}]", target)?;
+/// A cache of [`CallsitePrefix`]es, keyed by callsite.
+///
+/// Lookups only take a read lock, so that events from many threads do not wait for each
+/// other; the write lock is taken once per callsite, when it is first seen.
+#[derive(Default)]
+pub(crate) struct CallsiteCache {
+    prefixes: RwLock<HashMap<Identifier, Arc<CallsitePrefix>>>,
+}
+
+impl CallsiteCache {
+    /// Returns the cached prefix for `metadata`'s callsite, rendering it with `format` if this
+    /// is the first event from that callsite.
+    pub(crate) fn get_or_render(&self, format: FormatCallsite<'_>) -> Arc<CallsitePrefix> {
+        let callsite = format.metadata.callsite();
+        let prefixes = self.prefixes.read().unwrap_or_else(PoisonError::into_inner);
+        if let Some(prefix) = prefixes.get(&callsite) {
+            return prefix.clone();
         }
+        drop(prefixes);
 
-        write!(f, " {file}:{line}", file = file, line = line)?;
-        Ok(())
+        // The prefix is rendered without holding the lock. Another thread may have rendered
+        // the same callsite in the meantime, in which case its prefix is kept.
+        let prefix = Arc::new(CallsitePrefix::new(&format));
+        self.prefixes
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(callsite)
+            .or_insert(prefix)
+            .clone()
     }
 }
 
+/// The escape sequence that ends all styling.
+#[cfg(feature = "ansi")]
+const RESET: &str = "\x1b[0m";
+
 /// Docs!
 pub(crate) struct FormatSpanFields<'a> {
     span_name: &'static str,
     fields: Option<&'a str>,
     #[cfg(feature = "ansi")]
     pub ansi: bool,
+    #[cfg(feature = "ansi")]
+    theme: &'a Theme,
+    #[cfg(feature = "ansi")]
+    name_color: Option<Color>,
 }
 
 impl<'a> FormatSpanFields<'a> {
@@ -293,28 +1326,54 @@ impl<'a> FormatSpanFields<'a> {
         span_name: &'static str,
         fields: Option<&'a str>,
         ansi: bool,
+        theme: &'a Theme,
     ) -> Self {
         #[cfg(not(feature = "ansi"))]
-        let _ = ansi;
+        let _ = (ansi, theme);
         Self {
             span_name,
             fields,
             #[cfg(feature = "ansi")]
             ansi,
+            #[cfg(feature = "ansi")]
+            theme,
+            #[cfg(feature = "ansi")]
+            name_color: None,
         }
     }
+
+    /// Colors the span name, keeping the other attributes of its style.
+    #[cfg(feature = "ansi")]
+    pub(crate) fn with_name_color(self, name_color: Option<Color>) -> Self {
+        Self { name_color, ..self }
+    }
 }
 
 impl<'a> fmt::Display for FormatSpanFields<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         #[cfg(feature = "ansi")]
         if self.ansi {
-            let bold = Style::new().bold();
-            write!(f, "{}", bold.paint(self.span_name))?;
+            let style = match self.name_color {
+                Some(color) => self.theme.span_name.fg(color),
+                None => self.theme.span_name,
+            };
+            write!(f, "{}", style.paint(self.span_name))?;
 
-            let italic = Style::new().italic();
             if let Some(fields) = self.fields {
-                write!(f, "{{{}}}", italic.paint(fields))?;
+                // Styled field names end all styling, so the style of the fields is restored
+                // after each of them.
+                let style = self.theme.span_fields;
+                let mut pieces = fields.split(RESET);
+                write!(
+                    f,
+                    "{{{}{}",
+                    style.prefix(),
+                    pieces.next().unwrap_or_default()
+                )?;
+                for piece in pieces {
+                    write!(f, "{}{}{}", RESET, style.prefix(), piece)?;
+                }
+                write!(f, "{}}}", style.suffix())?;
             };
             return Ok(());
         }
@@ -326,3 +1385,592 @@ impl<'a> fmt::Display for FormatSpanFields<'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::format_with, Glog, GlogFields};
+    use std::thread;
+    use tracing_subscriber::{
+        fmt::{FmtContext, FormatEvent, FormatFields},
+        registry::LookupSpan,
+    };
+
+    /// Formats the events logged by `log` with `format`, and returns each line from the
+    /// thread name onwards.
+    fn lines_of(format: Glog, log: impl FnOnce()) -> Vec<String> {
+        format_with(format, GlogFields::default(), false, log)
+            .lines()
+            .map(|line| {
+                // Skip the level and date, the time and the right-aligned thread id, and the
+                // space after them.
+                let mut rest = line;
+                for _ in 0..3 {
+                    rest = rest.trim_start();
+                    rest = &rest[rest.find(' ').unwrap()..];
+                }
+                rest[1..].to_string()
+            })
+            .collect()
+    }
+
+    fn colons(c: char) -> bool {
+        c == ':'
+    }
+
+    fn slashes(c: char) -> bool {
+        c == '/'
+    }
+
+    #[test]
+    fn abbreviated_targets() {
+        let target = "a::b::c";
+        assert_eq!(abbreviate(target, colons, 7), "a::b::c");
+        assert_eq!(abbreviate(target, colons, 100), "a::b::c");
+        assert_eq!(abbreviate(target, colons, 6), "a::b::c");
+        assert_eq!(abbreviate(target, colons, 0), "a::b::c");
+
+        let target = "alpha::beta::gamma";
+        assert_eq!(abbreviate(target, colons, 18), "alpha::beta::gamma");
+        assert_eq!(abbreviate(target, colons, 17), "a::beta::gamma");
+        assert_eq!(abbreviate(target, colons, 14), "a::beta::gamma");
+        assert_eq!(abbreviate(target, colons, 13), "a::b::gamma");
+        assert_eq!(abbreviate(target, colons, 1), "a::b::gamma");
+
+        let target = "my_company::storage::backend::s3::client";
+        assert_eq!(abbreviate(target, colons, 20), "m::s::b::s3::client");
+        assert_eq!(abbreviate(target, colons, 30), "m::s::backend::s3::client");
+        assert_eq!(abbreviate("client", colons, 3), "client");
+    }
+
+    #[test]
+    fn abbreviated_files() {
+        let file = "src/runtime/scheduler/mod.rs";
+        assert_eq!(abbreviate(file, slashes, 28), file);
+        assert_eq!(abbreviate(file, slashes, 20), "s/r/scheduler/mod.rs");
+        assert_eq!(abbreviate(file, slashes, 10), "s/r/s/mod.rs");
+        assert_eq!(abbreviate("/abs/path.rs", slashes, 5), "/a/path.rs");
+    }
+
+    #[test]
+    fn abbreviated_multi_byte_names() {
+        assert_eq!(abbreviate("ünï::мод::c", colons, 7), "ü::м::c");
+        // Widths are counted in characters, not bytes.
+        assert_eq!(abbreviate("ünï::мод::c", colons, 11), "ünï::мод::c");
+        assert_eq!(abbreviate("ünï::мод::c", colons, 10), "ü::мод::c");
+    }
+
+    #[test]
+    fn truncation() {
+        assert_eq!(truncate_start("src/lib.rs".into(), None), "src/lib.rs");
+        assert_eq!(truncate_start("src/lib.rs".into(), Some(10)), "src/lib.rs");
+        assert_eq!(truncate_start("src/lib.rs".into(), Some(7)), "…lib.rs");
+        assert_eq!(truncate_start("src/lib.rs".into(), Some(1)), "…");
+        assert_eq!(truncate_start("src/lib.rs".into(), Some(0)), "…");
+
+        assert_eq!(truncate_end("worker-thread", None), "worker-thread");
+        assert_eq!(truncate_end("worker-thread", Some(13)), "worker-thread");
+        assert_eq!(truncate_end("worker-thread", Some(7)), "worker…");
+        assert_eq!(truncate_end("worker-thread", Some(1)), "…");
+    }
+
+    #[test]
+    fn truncation_of_multi_byte_names() {
+        assert_eq!(truncate_start("ünïcödé.rs".into(), Some(6)), "…dé.rs");
+        assert_eq!(truncate_start("ünïcödé.rs".into(), Some(10)), "ünïcödé.rs");
+        assert_eq!(truncate_end("потік-яка", Some(6)), "потік…");
+        assert_eq!(truncate_end("потік-яка", Some(9)), "потік-яка");
+    }
+
+    #[test]
+    fn fixed_columns() {
+        let column = Column::new(ColumnWidth::Fixed(8));
+        assert_eq!(column.limit(), Some(8));
+        assert_eq!(column.padding(3), 5);
+        assert_eq!(column.padding(8), 0);
+        assert_eq!(column.padding(12), 0);
+        assert_eq!(column.padding(3), 5);
+
+        let column = Column::default();
+        assert!(!column.is_enabled());
+        assert_eq!(column.limit(), None);
+        assert_eq!(column.padding(3), 0);
+    }
+
+    #[test]
+    fn adaptive_columns() {
+        let column = Column::new(ColumnWidth::Adaptive { max: 10 });
+        assert_eq!(column.limit(), Some(10));
+        assert_eq!(column.padding(4), 0);
+        assert_eq!(column.padding(2), 2);
+        // The column widens to the widest value written so far...
+        assert_eq!(column.padding(7), 0);
+        assert_eq!(column.padding(4), 3);
+        // ...but not past its maximum.
+        assert_eq!(column.padding(15), 0);
+        assert_eq!(column.padding(4), 6);
+
+        // Clones keep the width reached so far.
+        let clone = column.clone();
+        assert_eq!(clone.padding(4), 6);
+    }
+
+    #[test]
+    fn location_column_keeps_the_line_number() {
+        let columns = Columns::default().with_location(ColumnWidth::Fixed(12));
+        let format = Glog::default().with_columns(columns);
+        let mut line = 0;
+        let lines = lines_of(format, || {
+            line = line!() + 1;
+            tracing::info!("hello");
+        });
+
+        // The path gives way to the ellipsis, but `:line` is written in full.
+        let line = format!(":{}", line);
+        let file = truncate_start(file!().into(), Some(12 - line.len()));
+        assert!(file.starts_with(ELLIPSIS), "{}", file);
+        assert_eq!(lines, [format!("{}{}] hello", file, line)]);
+        let location = &lines[0][..lines[0].find(']').unwrap()];
+        assert_eq!(location.chars().count(), 12);
+    }
+
+    #[test]
+    fn location_column_pads_short_paths() {
+        let columns = Columns::default().with_location(ColumnWidth::Fixed(40));
+        let format = Glog::default().with_columns(columns);
+        let mut line = 0;
+        let lines = lines_of(format, || {
+            line = line!() + 1;
+            tracing::info!("hello");
+        });
+
+        let location = format!("{}:{}", file!(), line);
+        let padding = " ".repeat(40 - location.len());
+        assert_eq!(lines, [format!("{}{}] hello", padding, location)]);
+    }
+
+    #[test]
+    fn target_column_truncates_abbreviated_targets() {
+        let columns = Columns::default().with_target(ColumnWidth::Adaptive { max: 12 });
+        let format = Glog::default()
+            .with_target(true)
+            .with_abbreviated_target(Some(12))
+            .with_columns(columns);
+        let lines = lines_of(format, || {
+            tracing::info!(target: "alpha::beta::gamma", "first");
+            tracing::info!(target: "a::b", "second");
+            tracing::info!(target: "storage::backend::s3_client", "third");
+        });
+
+        let targets: Vec<&str> = lines
+            .iter()
+            .map(|line| &line[..line.find(" src/").unwrap()])
+            .collect();
+        assert_eq!(
+            targets,
+            ["[a::b::gamma]", "[a::b]       ", "[…::s3_client]"]
+        );
+    }
+
+    #[test]
+    fn thread_name_column_with_multi_byte_names() {
+        let columns = Columns::default().with_thread_name(ColumnWidth::Fixed(6));
+        let format = Glog::default()
+            .with_thread_names(true)
+            .with_columns(columns);
+        let lines = lines_of(format, || {
+            let dispatch = tracing::dispatcher::get_default(|dispatch| dispatch.clone());
+            thread::scope(|scope| {
+                for name in ["потік-яка", "як"] {
+                    thread::Builder::new()
+                        .name(name.to_string())
+                        .spawn_scoped(scope, || {
+                            tracing::dispatcher::with_default(&dispatch, || tracing::info!("hello"))
+                        })
+                        .unwrap()
+                        .join()
+                        .unwrap();
+                }
+            });
+        });
+
+        let names: Vec<&str> = lines
+            .iter()
+            .map(|line| &line[..line.find(" src/").unwrap()])
+            .collect();
+        assert_eq!(names, ["потік…", "як    "]);
+    }
+
+    /// Returns timestamps around second, day and year boundaries, in the order given.
+    fn tricky_times() -> Vec<Duration> {
+        // 2023-12-31 23:59:58 UTC, two seconds before a new year, and 2024-02-28 23:59:59,
+        // one second before a leap day.
+        [1_704_067_198, 1_709_164_799]
+            .into_iter()
+            .flat_map(|start| {
+                (0..4).flat_map(move |second| {
+                    let second = Duration::from_secs(start + second);
+                    [0, 1, 499_999, 999_998, 999_999]
+                        .into_iter()
+                        .map(move |micros| second + Duration::from_micros(micros))
+                })
+            })
+            .collect()
+    }
+
+    /// Formats `times` with both timers, and returns the pairs of timestamps.
+    fn cached_and_uncached(with_year: bool, times: &[Duration]) -> Vec<(String, String)> {
+        let (cached, uncached) = if with_year {
+            (CachedUtcTime::with_year(), UtcTime::with_year())
+        } else {
+            (CachedUtcTime::new(), UtcTime::default())
+        };
+        times
+            .iter()
+            .map(|&time| {
+                let mut from_cache = String::new();
+                cached
+                    .format_at(time, &mut Writer::new(&mut from_cache))
+                    .unwrap();
+                let mut expected = String::new();
+                let time =
+                    OffsetDateTime::from_unix_timestamp_nanos(time.as_nanos() as i128).unwrap();
+                format_datetime(&mut Writer::new(&mut expected), time, &uncached.format).unwrap();
+                (from_cache, expected)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cached_utc_time_agrees_with_utc_time() {
+        let mut times = tricky_times();
+        for with_year in [false, true] {
+            for (cached, uncached) in cached_and_uncached(with_year, &times) {
+                assert_eq!(cached, uncached);
+            }
+        }
+        assert_eq!(
+            cached_and_uncached(true, &times[..1])[0].0,
+            "20231231 23:59:58.000000"
+        );
+
+        // Going back in time, and switching between the two layouts within one second,
+        // must not reuse a stale rendering.
+        times.reverse();
+        for (i, &time) in times.iter().enumerate() {
+            let (cached, uncached) = cached_and_uncached(i % 2 == 0, &[time]).remove(0);
+            assert_eq!(cached, uncached);
+        }
+    }
+
+    #[test]
+    fn cached_utc_time_agrees_with_utc_time_across_threads() {
+        let times = tricky_times();
+        thread::scope(|scope| {
+            for thread in 0..4 {
+                let times = &times;
+                scope.spawn(move || {
+                    // Each thread walks the times from a different starting point, so that
+                    // their caches hold different seconds.
+                    let mut times = times.clone();
+                    times.rotate_left(thread * 7);
+                    for _ in 0..50 {
+                        for with_year in [false, true] {
+                            for (cached, uncached) in cached_and_uncached(with_year, &times) {
+                                assert_eq!(cached, uncached);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    /// A [`Glog`] that can be inspected after being given to a subscriber.
+    struct Shared(Arc<Glog>);
+
+    impl<S, N> FormatEvent<S, N> for Shared
+    where
+        S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+        N: for<'a> FormatFields<'a> + 'static,
+    {
+        fn format_event(
+            &self,
+            ctx: &FmtContext<'_, S, N>,
+            writer: Writer<'_>,
+            event: &tracing::Event<'_>,
+        ) -> fmt::Result {
+            self.0.format_event(ctx, writer, event)
+        }
+    }
+
+    /// Returns the lines of `output` after the thread id, with their line numbers removed.
+    fn without_line_numbers(output: &str) -> Vec<String> {
+        output
+            .lines()
+            .map(|line| {
+                // Skip the level and date, the time and the right-aligned id.
+                let rest = line
+                    .split_whitespace()
+                    .skip(3)
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let (before, after) = rest.split_once("format.rs:").unwrap();
+                let (_, message) = after.split_once("] ").unwrap();
+                format!("{}format.rs:N] {}", before, message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cached_prefixes_follow_the_target_switch() {
+        let glog = Arc::new(Glog::default());
+        let handle = glog.reload_handle();
+        let switches = [false, true, true, false];
+        let output = format_with(Shared(glog.clone()), GlogFields::default(), false, || {
+            for with_target in switches {
+                handle.set_target(with_target);
+                tracing::info!("hello");
+            }
+        });
+        let uncached: Vec<String> = switches
+            .into_iter()
+            .flat_map(|with_target| {
+                let output = format_with(
+                    Glog::default().with_target(with_target),
+                    GlogFields::default(),
+                    false,
+                    || tracing::info!("hello"),
+                );
+                without_line_numbers(&output)
+            })
+            .collect();
+        assert_eq!(without_line_numbers(&output), uncached);
+        assert_eq!(uncached[0], "src/format.rs:N] hello");
+        assert_eq!(
+            uncached[1],
+            "[tracing_glog::format::tests] src/format.rs:N] hello"
+        );
+        // One entry for the callsite, whichever way the switch was set.
+        assert_eq!(glog.callsites.prefixes.read().unwrap().len(), 1);
+    }
+
+    /// Formats an event in a span with `theme`, a fixed timestamp and ANSI escapes, and
+    /// returns the line with the process id replaced by `ID` and the line number by `N`.
+    #[cfg(feature = "ansi")]
+    fn themed_line(theme: Theme) -> String {
+        fn fixed(writer: &mut Writer<'_>) -> fmt::Result {
+            writer.write_str("1019 00:50:42.806849")
+        }
+
+        let mut line = 0;
+        let output = format_with(
+            Glog::default()
+                .with_timer(fixed as fn(&mut Writer<'_>) -> fmt::Result)
+                .with_theme(theme),
+            GlogFields::default().with_theme(theme),
+            true,
+            || {
+                let _span = tracing::info_span!("shave", yak = 1).entered();
+                line = line!() + 1;
+                tracing::info!(count = 2, "hello");
+            },
+        );
+        output
+            .replacen(&format!(" {:>5}", std::process::id()), " ID", 1)
+            .replacen(&format!("format.rs:{}", line), "format.rs:N", 1)
+    }
+
+    #[test]
+    #[cfg(feature = "ansi")]
+    fn theme_escapes() {
+        assert_eq!(
+            themed_line(Theme::light()),
+            "\x1b[32mI\x1b[0m\x1b[90m1019 00:50:42.806849\x1b[0m ID\x1b[1;30m src/format.rs:N\x1b[0m] \
+             [\x1b[1;34mshave\x1b[0m{\x1b[34m\x1b[35myak\x1b[0m\x1b[34m: 1\x1b[0m}] \
+             hello, \x1b[35mcount\x1b[0m: 2\n"
+        );
+        assert_eq!(
+            themed_line(Theme::dark()),
+            "\x1b[32mI\x1b[0m\x1b[90m1019 00:50:42.806849\x1b[0m ID\x1b[1;37m src/format.rs:N\x1b[0m] \
+             [\x1b[1;96mshave\x1b[0m{\x1b[36m\x1b[94myak\x1b[0m\x1b[36m: 1\x1b[0m}] \
+             hello, \x1b[94mcount\x1b[0m: 2\n"
+        );
+        // Plain styles write no escapes at all.
+        assert_eq!(
+            themed_line(Theme::monochrome_bold()),
+            "\x1b[32mI\x1b[0m1019 00:50:42.806849 ID\x1b[1m src/format.rs:N\x1b[0m] \
+             [\x1b[1mshave\x1b[0m{\x1b[1myak\x1b[0m: 1}] \
+             hello, \x1b[1mcount\x1b[0m: 2\n"
+        );
+    }
+
+    /// Formats `time` with `timer`'s format description.
+    fn utc_time_at<F: Formattable>(timer: &UtcTime<F>, time: OffsetDateTime) -> String {
+        let mut formatted = String::new();
+        format_datetime(&mut Writer::new(&mut formatted), time, &timer.format).unwrap();
+        formatted
+    }
+
+    #[test]
+    fn utc_time_layouts() {
+        // 2023-03-04 05:06:07.000089012 UTC.
+        let time = OffsetDateTime::from_unix_timestamp_nanos(1_677_906_367_000_089_012).unwrap();
+        assert_eq!(
+            utc_time_at(&UtcTime::default(), time),
+            "0304 05:06:07.000089"
+        );
+        assert_eq!(
+            utc_time_at(&UtcTime::with_year(), time),
+            "20230304 05:06:07.000089"
+        );
+        assert_eq!(
+            utc_time_at(&UtcTime::nanos(), time),
+            "0304 05:06:07.000089012"
+        );
+        let custom = UtcTime::new(format_description!("[hour]h[minute]"));
+        assert_eq!(utc_time_at(&custom, time), "05h06");
+    }
+
+    #[test]
+    fn utc_time_writes_the_current_time() {
+        let format_time = |timer: &dyn FormatTime| {
+            let mut formatted = String::new();
+            timer.format_time(&mut Writer::new(&mut formatted)).unwrap();
+            formatted
+        };
+        let before = OffsetDateTime::now_utc();
+        let default = format_time(&UtcTime::default());
+        let with_year = format_time(&UtcTime::with_year());
+        let nanos = format_time(&UtcTime::nanos());
+        let after = OffsetDateTime::now_utc();
+
+        // Each timestamp lies between the ones taken before and after it, which compare in
+        // the same order as their strings.
+        for (formatted, timer) in [
+            (default, UtcTime::default()),
+            (with_year, UtcTime::with_year()),
+            (nanos, UtcTime::nanos()),
+        ] {
+            let [before, after] = [before, after].map(|time| utc_time_at(&timer, time));
+            assert_eq!(formatted.len(), before.len());
+            assert!(before <= formatted && formatted <= after, "{}", formatted);
+        }
+    }
+
+    /// Formats `time` with a glog-style `timer`.
+    fn local_time_at(timer: &LocalTime, time: OffsetDateTime) -> String {
+        let mut formatted = String::new();
+        timer
+            .format_at(time, &mut Writer::new(&mut formatted))
+            .unwrap();
+        formatted
+    }
+
+    fn captured(
+        lookup: fn(OffsetDateTime) -> Option<UtcOffset>,
+        refresh: Option<Duration>,
+    ) -> LocalTime {
+        LocalTime {
+            offset: LocalOffset::Captured(Arc::new(CapturedOffset::capture_with(lookup, refresh))),
+            ..LocalTime::default()
+        }
+    }
+
+    #[test]
+    fn local_time_offsets() {
+        // 2023-03-04 05:06:07.000089 UTC.
+        let time = OffsetDateTime::from_unix_timestamp_nanos(1_677_906_367_000_089_000).unwrap();
+        let plus_three = captured(|_| UtcOffset::from_hms(3, 0, 0).ok(), None);
+        assert_eq!(local_time_at(&plus_three, time), "0304 08:06:07.000089");
+
+        let zoned = LocalTime {
+            offset: LocalOffset::Current(Arc::new(TimeZone::from_posix("EST5").ok().into())),
+            ..LocalTime::default()
+        };
+        assert_eq!(local_time_at(&zoned, time), "0304 00:06:07.000089");
+
+        // Without an offset, the timestamp is in UTC and keeps glog's layout.
+        let unknown = captured(|_| None, None);
+        assert_eq!(local_time_at(&unknown, time), "0304 05:06:07.000089");
+    }
+
+    #[test]
+    fn local_time_refreshes_the_offset() {
+        static OFFSET: AtomicI32 = AtomicI32::new(CapturedOffset::UNKNOWN);
+        fn lookup(_: OffsetDateTime) -> Option<UtcOffset> {
+            UtcOffset::from_whole_seconds(OFFSET.load(Ordering::Relaxed)).ok()
+        }
+
+        let time = OffsetDateTime::from_unix_timestamp_nanos(1_677_906_367_000_089_000).unwrap();
+        let refreshed = captured(lookup, Some(Duration::ZERO));
+        let fixed = captured(lookup, None);
+        assert_eq!(local_time_at(&refreshed, time), "0304 05:06:07.000089");
+
+        OFFSET.store(-2 * 3600, Ordering::Relaxed);
+        assert_eq!(local_time_at(&refreshed, time), "0304 03:06:07.000089");
+        assert_eq!(local_time_at(&fixed, time), "0304 05:06:07.000089");
+
+        // A failed lookup keeps the last known offset.
+        OFFSET.store(CapturedOffset::UNKNOWN, Ordering::Relaxed);
+        assert_eq!(local_time_at(&refreshed, time), "0304 03:06:07.000089");
+    }
+
+    /// Logs one event of each level with `levels`, and returns the start of each line up to
+    /// its timestamp.
+    fn severities(levels: LevelStyle, ansi: bool) -> Vec<String> {
+        let glog = Glog::default()
+            .with_level_style(levels)
+            .with_timer(UtcTime::new(format_description!("|")));
+        format_with(glog, GlogFields::default(), ansi, || {
+            tracing::trace!("");
+            tracing::debug!("");
+            tracing::info!("");
+            tracing::warn!("");
+            tracing::error!("");
+        })
+        .lines()
+        .map(|line| line[..line.find('|').unwrap()].to_string())
+        .collect()
+    }
+
+    #[test]
+    fn level_characters() {
+        let levels = LevelStyle::default()
+            .with_char(Level::TRACE, 'V')
+            .with_char(Level::DEBUG, 'V');
+        assert_eq!(severities(levels, false), ["V", "V", "I", "W", "E"]);
+    }
+
+    #[test]
+    #[cfg(feature = "ansi")]
+    fn level_styles() {
+        // Timestamps are dimmed by the default theme.
+        assert_eq!(
+            severities(LevelStyle::default(), true),
+            [
+                "\x1b[35mT\x1b[0m\x1b[2m",
+                "\x1b[34mD\x1b[0m\x1b[2m",
+                "\x1b[32mI\x1b[0m\x1b[2m",
+                "\x1b[33mW\x1b[0m\x1b[2m",
+                "\x1b[31mE\x1b[0m\x1b[2m",
+            ]
+        );
+
+        let levels = LevelStyle::default()
+            .with_char(Level::TRACE, 'V')
+            .with_style(Level::TRACE, Color::Cyan.normal())
+            .with_style(Level::ERROR, Color::White.on(Color::Red).bold())
+            .with_style(Level::INFO, Style::new());
+        assert_eq!(
+            severities(levels, true),
+            [
+                "\x1b[36mV\x1b[0m\x1b[2m",
+                "\x1b[34mD\x1b[0m\x1b[2m",
+                "I\x1b[2m",
+                "\x1b[33mW\x1b[0m\x1b[2m",
+                "\x1b[1;41;37mE\x1b[0m\x1b[2m",
+            ]
+        );
+    }
+}
@@ -0,0 +1,482 @@
+use crate::{
+    shutdown::{self, Flush, Stage},
+    Glog, GlogFields, SpanFields, UtcTime,
+};
+use std::{
+    cell::RefCell,
+    fmt,
+    io::{self, Write},
+    sync::{Arc, Mutex, MutexGuard, Once, PoisonError, Weak},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{
+    span::{Attributes, Id, Record},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{
+    field::{RecordFields, VisitOutput},
+    fmt::{format::Writer, time::FormatTime, MakeWriter},
+    layer::Context,
+    registry::LookupSpan,
+    Layer,
+};
+
+/// A [`Layer`] that formats events with [`Glog`] and writes them to its own writer, without
+/// going through [`tracing_subscriber::fmt`](mod@tracing_subscriber::fmt).
+///
+/// `GlogLayer` renders span fields with [`GlogFields`] into its own span extension, so it
+/// composes with any other layers (including `fmt::Layer`s configured with other field
+/// formatters) without relying on [`FormattedFields`] being present. By default, events are
+/// written to stderr, as glog does.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_subscriber::prelude::*;
+/// use tracing_subscriber::Registry;
+/// use tracing_glog::{Glog, GlogLayer};
+///
+/// let layer = GlogLayer::default()
+///     .event_format(Glog::default().with_thread_names(true))
+///     .with_writer(std::io::stdout)
+///     .with_batch_size(64 * 1024);
+///
+/// let subscriber = Registry::default().with(layer);
+/// tracing::subscriber::set_global_default(subscriber).expect("Unable to set global subscriber");
+/// ```
+///
+/// [`FormattedFields`]: tracing_subscriber::fmt::FormattedFields
+pub struct GlogLayer<W = fn() -> io::Stderr, T = UtcTime>
+where
+    W: for<'w> MakeWriter<'w>,
+{
+    format: Glog<T>,
+    fields: GlogFields,
+    ansi: bool,
+    sink: Arc<Sink<W>>,
+}
+
+/// Where formatted events go: straight to the writer, or into a batch that is written when
+/// it is full or too old, when it is flushed (see [`flush`](crate::flush)), and when the
+/// sink is dropped.
+struct Sink<W>
+where
+    W: for<'w> MakeWriter<'w>,
+{
+    make_writer: W,
+    batch: Option<Mutex<Batch>>,
+    max_age: Duration,
+    /// Registers the sink with [`shutdown`] and starts the thread that writes out old
+    /// batches, once the first line is batched.
+    started: Once,
+}
+
+/// Formatted lines waiting to be written together.
+struct Batch {
+    buf: Vec<u8>,
+    capacity: usize,
+    /// When the first line of `buf` was batched.
+    oldest: Option<Instant>,
+}
+
+/// How long lines are batched for by default, like glog's `--logbufsecs`.
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(30);
+
+/// The fields of a span, as rendered by a [`GlogLayer`].
+struct GlogSpanFields(String);
+
+impl SpanFields for GlogSpanFields {
+    fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for GlogLayer {
+    fn default() -> Self {
+        GlogLayer {
+            format: Glog::default(),
+            fields: GlogFields::default(),
+            ansi: false,
+            sink: Arc::new(Sink {
+                make_writer: io::stderr,
+                batch: None,
+                max_age: DEFAULT_MAX_AGE,
+                started: Once::new(),
+            }),
+        }
+    }
+}
+
+impl<W, T> GlogLayer<W, T>
+where
+    W: for<'w> MakeWriter<'w>,
+{
+    /// Sets the [`Glog`] formatter used for events.
+    pub fn event_format<T2>(self, format: Glog<T2>) -> GlogLayer<W, T2>
+    where
+        T2: FormatTime,
+    {
+        GlogLayer {
+            format,
+            fields: self.fields,
+            ansi: self.ansi,
+            sink: self.sink,
+        }
+    }
+
+    /// Sets the [`GlogFields`] used to render span and event fields.
+    pub fn fmt_fields(self, fields: GlogFields) -> Self {
+        GlogLayer { fields, ..self }
+    }
+
+    /// Sets the [`MakeWriter`] that events are written to.
+    pub fn with_writer<W2>(mut self, make_writer: W2) -> GlogLayer<W2, T>
+    where
+        W2: for<'w> MakeWriter<'w> + 'static,
+    {
+        let batch = self.sink_mut().batch.take();
+        let max_age = self.sink.max_age;
+        GlogLayer {
+            format: self.format,
+            fields: self.fields,
+            ansi: self.ansi,
+            sink: Arc::new(Sink {
+                make_writer,
+                batch,
+                max_age,
+                started: Once::new(),
+            }),
+        }
+    }
+
+    /// Sets whether or not ANSI escapes are written. Defaults to false.
+    ///
+    /// An [`AnsiMode`] set with [`Glog::with_ansi_mode`] or [`GlogFields::with_ansi_mode`]
+    /// takes precedence over this setting.
+    ///
+    /// [`AnsiMode`]: crate::AnsiMode
+    pub fn with_ansi(self, ansi: bool) -> Self {
+        GlogLayer { ansi, ..self }
+    }
+
+    /// Buffers formatted events and writes them in batches of roughly `bytes` bytes.
+    ///
+    /// Batching trades latency for fewer, larger writes. Buffered events are written when the
+    /// batch is full, when its oldest line has waited for longer than the maximum age (see
+    /// [`GlogLayer::with_batch_max_age`]), when [`flush`] or [`shutdown`] is called, including
+    /// by a [`ShutdownGuard`], and when the layer is dropped. A batch is written with
+    /// [`MakeWriter::make_writer`], so per-event writer selection with
+    /// [`MakeWriter::make_writer_for`] does not apply to batched events. A size of 0 disables
+    /// batching.
+    ///
+    /// A subscriber installed with `init` is never dropped, so hold a [`ShutdownGuard`] in
+    /// `main` to write out the last batch.
+    ///
+    /// [`flush`]: crate::flush
+    /// [`shutdown`]: crate::shutdown
+    /// [`ShutdownGuard`]: crate::ShutdownGuard
+    pub fn with_batch_size(mut self, bytes: usize) -> Self {
+        self.sink_mut().batch = if bytes == 0 {
+            None
+        } else {
+            Some(Mutex::new(Batch {
+                buf: Vec::with_capacity(bytes),
+                capacity: bytes,
+                oldest: None,
+            }))
+        };
+        self
+    }
+
+    /// Sets how long a line may wait in a batch before the batch is written, so that lines
+    /// still show up when few events are logged. Defaults to 30 seconds, like glog's
+    /// `--logbufsecs`.
+    ///
+    /// This only applies if batching is enabled with [`GlogLayer::with_batch_size`]. Old
+    /// batches are written by a background thread, which is started when the first line is
+    /// batched.
+    pub fn with_batch_max_age(mut self, max_age: Duration) -> Self {
+        self.sink_mut().max_age = max_age;
+        self
+    }
+
+    fn sink_mut(&mut self) -> &mut Sink<W> {
+        // The sink is only shared once events are written, after the layer is built.
+        Arc::get_mut(&mut self.sink).expect("the layer is not in use while it is built")
+    }
+
+    /// Whether the prefix is styled, as overridden by the formatter's [`AnsiMode`].
+    ///
+    /// [`AnsiMode`]: crate::AnsiMode
+    fn ansi(&self) -> bool {
+        self.format.ansi.unwrap_or(self.ansi)
+    }
+
+    /// Whether fields are styled, as overridden by the field formatter's or the formatter's
+    /// [`AnsiMode`].
+    ///
+    /// [`AnsiMode`]: crate::AnsiMode
+    fn fields_ansi(&self) -> bool {
+        self.fields.ansi.or(self.format.ansi).unwrap_or(self.ansi)
+    }
+
+    fn render_fields<R>(&self, into: &mut String, fields: R) -> fmt::Result
+    where
+        R: RecordFields,
+    {
+        let is_empty = into.is_empty();
+        let mut visitor = self
+            .fields
+            .visitor(Writer::new(into), self.fields_ansi(), is_empty);
+        fields.record(&mut visitor);
+        visitor.finish()
+    }
+}
+
+impl<S, W, T> Layer<S> for GlogLayer<W, T>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+    T: FormatTime + 'static,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).expect("Span not found, this is a bug");
+        let mut extensions = span.extensions_mut();
+        if extensions.get_mut::<GlogSpanFields>().is_none() {
+            let mut fields = String::new();
+            if self.render_fields(&mut fields, attrs).is_ok() {
+                extensions.insert(GlogSpanFields(fields));
+            }
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let span = ctx.span(id).expect("Span not found, this is a bug");
+        let mut extensions = span.extensions_mut();
+        if let Some(GlogSpanFields(fields)) = extensions.get_mut::<GlogSpanFields>() {
+            let _ = self.render_fields(fields, values);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        thread_local! {
+            static BUF: RefCell<String> = const { RefCell::new(String::new()) };
+        }
+
+        BUF.with(|buf| {
+            let mut fresh = String::new();
+            let mut borrowed = buf.try_borrow_mut();
+            // An event emitted while formatting another one gets a fresh buffer.
+            let buf = match borrowed {
+                Ok(ref mut buf) => &mut **buf,
+                Err(_) => &mut fresh,
+            };
+
+            let formatted = self.format.format_event_with::<_, GlogSpanFields>(
+                Writer::new(buf),
+                event,
+                self.ansi(),
+                ctx.event_span(event),
+                |writer| self.render_event_fields(writer, event),
+            );
+            if formatted.is_ok() {
+                self.sink.write(event, buf.as_bytes());
+            }
+            buf.clear();
+        });
+    }
+}
+
+impl<W, T> GlogLayer<W, T>
+where
+    W: for<'w> MakeWriter<'w>,
+{
+    fn render_event_fields(&self, writer: &mut Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let mut visitor = self
+            .fields
+            .visitor(writer.by_ref(), self.fields_ansi(), true);
+        event.record(&mut visitor);
+        visitor.finish()
+    }
+}
+
+impl<W> Sink<W>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    fn write(self: &Arc<Self>, event: &Event<'_>, line: &[u8]) {
+        match &self.batch {
+            Some(batch) => {
+                self.started.call_once(|| self.start());
+                let mut batch = lock(batch);
+                if batch.buf.is_empty() {
+                    batch.oldest = Some(Instant::now());
+                }
+                batch.buf.extend_from_slice(line);
+                if batch.buf.len() >= batch.capacity {
+                    self.write_batch(&mut batch);
+                }
+            }
+            None => {
+                let _ = self
+                    .make_writer
+                    .make_writer_for(event.metadata())
+                    .write_all(line);
+            }
+        }
+    }
+
+    fn start(self: &Arc<Self>) {
+        let sink: Weak<dyn Flush> = Arc::downgrade(self) as _;
+        shutdown::register(Stage::Batch, sink);
+
+        let sink = Arc::downgrade(self);
+        let _ = thread::Builder::new()
+            .name("glog-batch".into())
+            .spawn(move || Self::write_old_batches(sink));
+    }
+
+    /// Writes out batches whose oldest line has waited for longer than the maximum age,
+    /// until the sink is dropped.
+    fn write_old_batches(sink: Weak<Self>) {
+        let mut wait = Duration::ZERO;
+        loop {
+            // A maximum age of zero would otherwise keep this thread busy.
+            thread::sleep(wait.max(Duration::from_millis(10)));
+            let Some(sink) = sink.upgrade() else {
+                return;
+            };
+            let Some(batch) = &sink.batch else {
+                return;
+            };
+            let mut batch = lock(batch);
+            let age = batch
+                .oldest
+                .map_or(Duration::ZERO, |oldest| oldest.elapsed());
+            wait = if age >= sink.max_age && !batch.buf.is_empty() {
+                sink.write_batch(&mut batch);
+                sink.max_age
+            } else {
+                sink.max_age - age
+            };
+        }
+    }
+}
+
+impl<W> Sink<W>
+where
+    W: for<'w> MakeWriter<'w>,
+{
+    fn write_batch(&self, batch: &mut Batch) {
+        if !batch.buf.is_empty() {
+            let mut writer = self.make_writer.make_writer();
+            let _ = writer.write_all(&batch.buf);
+            let _ = writer.flush();
+            batch.buf.clear();
+        }
+        batch.oldest = None;
+    }
+}
+
+impl<W> Flush for Sink<W>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync,
+{
+    fn flush(&self, _: Level) {
+        // Lines of all levels share the batch, so they are all written out.
+        if let Some(batch) = &self.batch {
+            self.write_batch(&mut lock(batch));
+        }
+    }
+
+    fn shutdown(&self) {
+        self.flush(Level::TRACE);
+    }
+}
+
+impl<W> Drop for Sink<W>
+where
+    W: for<'w> MakeWriter<'w>,
+{
+    fn drop(&mut self) {
+        if let Some(batch) = self.batch.take() {
+            let mut batch = batch.into_inner().unwrap_or_else(PoisonError::into_inner);
+            self.write_batch(&mut batch);
+        }
+    }
+}
+
+fn lock(batch: &Mutex<Batch>) -> MutexGuard<'_, Batch> {
+    batch.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Capture;
+    #[cfg(feature = "ansi")]
+    use crate::AnsiMode;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn flush_writes_the_batch() {
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let layer = GlogLayer::default()
+            .with_writer(move || writer.clone())
+            .with_batch_size(64 * 1024);
+        // The subscriber is kept alive, as one installed with `init` would be.
+        let subscriber = Arc::new(tracing_subscriber::registry().with(layer));
+        tracing::subscriber::with_default(subscriber.clone(), || tracing::info!("hello"));
+        assert_eq!(capture.contents(), "");
+
+        shutdown::flush(Level::TRACE);
+        assert!(capture.contents().ends_with("] hello\n"));
+    }
+
+    #[test]
+    fn old_batches_are_written() {
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let layer = GlogLayer::default()
+            .with_batch_max_age(Duration::from_millis(20))
+            .with_writer(move || writer.clone())
+            .with_batch_size(64 * 1024);
+        let subscriber = Arc::new(tracing_subscriber::registry().with(layer));
+        tracing::subscriber::with_default(subscriber.clone(), || tracing::info!("hello"));
+
+        for _ in 0..100 {
+            if !capture.contents().is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(capture.contents().ends_with("] hello\n"));
+    }
+
+    #[test]
+    #[cfg(feature = "ansi")]
+    fn formatter_ansi_mode_overrides_the_layer() {
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let layer = GlogLayer::default()
+            .event_format(Glog::default().with_ansi_mode(AnsiMode::Always, &io::stderr()))
+            .with_writer(move || writer.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || tracing::info!(yak = 1, "hello"));
+        let contents = capture.contents();
+        assert!(contents.starts_with("\x1b["), "{:?}", contents);
+        assert!(contents.contains("\x1b[1myak\x1b[0m"), "{:?}", contents);
+
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let layer = GlogLayer::default()
+            .event_format(Glog::default().with_ansi_mode(AnsiMode::Never, &io::stderr()))
+            .with_ansi(true)
+            .with_writer(move || writer.clone());
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || tracing::info!(yak = 1, "hello"));
+        assert!(!capture.contents().contains('\x1b'));
+    }
+}
@@ -76,22 +76,54 @@
 //! <div class="example-wrap" style="display:inline-block">
 //! <pre class="compile_fail" style="white-space:normal;font:inherit;">
 //!     <strong>Warning</strong>: The <a href = "https://docs.rs/time/0.3/time/"><code>time</code>
-//!     crate</a> must be compiled with <code>--cfg unsound_local_offset</code> in order to use
-//!     `LocalTime`. When this cfg is not enabled, local timestamps cannot be recorded, and
-//!     no events will be emitted.
+//!     crate</a> must be compiled with <code>--cfg unsound_local_offset</code> in order to
+//!     look up the local offset for every event where the local time zone cannot be read
+//!     from <code>TZ</code> or <code>/etc/localtime</code>. When this cfg is not enabled,
+//!     such events are recorded with UTC timestamps, unless the offset is captured during
+//!     initialization with <code>LocalTime::from_startup_offset</code>.
 //!
 //!    See the <a href="https://docs.rs/time/0.3.9/time/#feature-flags"><code>time</code>
 //!    documentation</a> for more details.
 //! </pre></div>
 //!
+//! # Performance
+//!
+//! Once a callsite has been seen, formatting an event whose fields are all primitives does
+//! not allocate: the target and `file:line` are rendered once per callsite, ANSI styling is
+//! skipped entirely when it is disabled, and [`fmt::Layer`] formats into a reusable
+//! thread-local buffer. For the lowest per-event cost, use [`CachedUtcTime`] as the timer.
+//! The `format` and `timer` benchmarks (`cargo bench`) compare `Glog` against [`Full`] and
+//! fail if the hot path starts allocating.
+//!
 //! [glog]: https://github.com/google/glog
 //! [`tracing-subscriber`]: https://docs.rs/tracing-subscriber
 //! [`fmt::Subscriber`]: tracing_subscriber::fmt::Subscriber
 //! [`fmt::Layer`]: tracing_subscriber::fmt::Layer
 //! [`Full`]: tracing_subscriber::fmt::format::Full
+#![deny(rustdoc::broken_intra_doc_links)]
 
-#[deny(rustdoc::broken_intra_doc_links)]
+mod ansi;
+#[cfg(feature = "structopt")]
+mod args;
+#[cfg(feature = "ansi")]
+mod colors;
+mod config;
+#[cfg(feature = "config-file")]
+mod config_file;
+mod dialect;
+mod files;
 mod format;
+mod layer;
+#[cfg(feature = "ansi")]
+mod links;
+mod non_blocking;
+mod reload;
+mod shutdown;
+#[cfg(test)]
+mod testing;
+mod tz;
+mod uptime;
+mod writer;
 
 #[cfg(feature = "ansi")]
 mod nu_ansi_term {
@@ -100,7 +132,7 @@ mod nu_ansi_term {
 #[cfg(not(feature = "ansi"))]
 mod nu_ansi_term {
     // Minimal API shim for nu_ansi_term to avoid a pile of #[cfg(feature = "ansi")] directives.
-    #[derive(Copy, Clone)]
+    #[derive(Copy, Clone, Debug)]
     pub struct Style;
 
     impl Style {
@@ -110,10 +142,13 @@ mod nu_ansi_term {
         pub fn bold(&self) -> Self {
             Style
         }
-        pub fn prefix(&self) -> &'static str {
-            ""
+        pub fn dimmed(&self) -> Self {
+            Style
         }
-        pub fn infix(&self, _: Style) -> &'static str {
+        pub fn italic(&self) -> Self {
+            Style
+        }
+        pub fn prefix(&self) -> &'static str {
             ""
         }
         pub fn suffix(&self) -> &'static str {
@@ -122,10 +157,32 @@ mod nu_ansi_term {
     }
 }
 
+#[cfg(not(feature = "ansi"))]
 use crate::nu_ansi_term::Style;
+/// Re-exported from [`nu_ansi_term`](::nu_ansi_term) to build the styles of [`LevelStyle`].
+#[cfg(feature = "ansi")]
+pub use ::nu_ansi_term::{Color, Style};
+pub use ansi::AnsiMode;
+#[cfg(feature = "structopt")]
+pub use args::GlogArgs;
+#[cfg(feature = "ansi")]
+pub use colors::{ColorDepth, NameColors};
+pub use config::{init_from_env, ConfigError, GlogFilter, GlogFilterHandle, GlogOptions};
+#[cfg(feature = "config-file")]
+pub use config_file::GlogConfig;
+pub use dialect::GlogDialect;
+use dialect::Quoted;
+pub use files::{LogFiles, LogFilesWriter};
 use format::FmtLevel;
-pub use format::{LocalTime, UtcTime};
-use std::fmt;
+pub use format::{CachedUtcTime, ColumnWidth, Columns, LevelStyle, LocalTime, Theme, UtcTime};
+pub use layer::GlogLayer;
+#[cfg(feature = "ansi")]
+pub use links::FileLinks;
+pub use non_blocking::{BackPressure, NonBlocking, NonBlockingBuilder, WorkerGuard};
+pub use reload::GlogHandle;
+use reload::{Snapshot, Switches};
+pub use shutdown::{flush, shutdown, ShutdownGuard};
+use std::{fmt, io::IsTerminal, sync::Arc};
 use tracing::{
     field::{Field, Visit},
     Subscriber,
@@ -137,21 +194,47 @@ use tracing_subscriber::{
     fmt::{
         format::Writer, time::FormatTime, FmtContext, FormatEvent, FormatFields, FormattedFields,
     },
-    registry::LookupSpan,
+    registry::{LookupSpan, SpanRef},
 };
+pub use tz::{TimeZone, TzError, ZonedTime};
+pub use uptime::{Monotonic, Uptime};
+pub use writer::{GlogEventWriter, GlogWriter};
 
-use crate::format::{FormatProcessData, FormatSpanFields};
+use crate::format::{
+    CallsiteCache, CargoPaths, FormatCallsite, FormatProcessData, FormatSpanFields,
+};
 
 /// A [glog]-inspired span and event formatter.
 ///
 /// [glog]: https://github.com/google/glog
 pub struct Glog<T = UtcTime> {
     timer: T,
-    with_span_context: bool,
-    with_thread_names: bool,
-    with_target: bool,
+    switches: Arc<Switches>,
+    with_prefix: bool,
     with_trimmed_directory: bool,
-    with_strip_prefix: Option<String>,
+    /// Sorted from the longest to the shortest.
+    strip_prefixes: Vec<String>,
+    cargo_paths: Option<CargoPaths>,
+    target_abbreviation: Option<usize>,
+    file_abbreviation: Option<usize>,
+    columns: Columns,
+    /// The settings that were set explicitly, which [`Glog::with_dialect`] keeps.
+    overridden: Overridden,
+    pid_width: usize,
+    /// Whether the id column holds the thread id rather than the process id.
+    with_thread_id: bool,
+    bracketed_timer: Option<Box<dyn FormatTime + Send + Sync>>,
+    level_style: LevelStyle,
+    theme: Theme,
+    /// Overrides whether the writer has ANSI escapes enabled.
+    ansi: Option<bool>,
+    #[cfg(feature = "ansi")]
+    target_colors: Option<NameColors>,
+    #[cfg(feature = "ansi")]
+    span_colors: Option<NameColors>,
+    #[cfg(feature = "ansi")]
+    file_links: Option<FileLinks>,
+    callsites: CallsiteCache,
 }
 
 impl<T> Glog<T> {
@@ -167,29 +250,103 @@ impl<T> Glog<T> {
     {
         Glog {
             timer,
-            with_thread_names: self.with_thread_names,
-            with_target: self.with_target,
-            with_span_context: self.with_span_context,
+            switches: self.switches,
+            with_prefix: self.with_prefix,
             with_trimmed_directory: self.with_trimmed_directory,
-            with_strip_prefix: self.with_strip_prefix,
+            strip_prefixes: self.strip_prefixes,
+            cargo_paths: self.cargo_paths,
+            target_abbreviation: self.target_abbreviation,
+            file_abbreviation: self.file_abbreviation,
+            columns: self.columns,
+            overridden: self.overridden,
+            pid_width: self.pid_width,
+            with_thread_id: self.with_thread_id,
+            bracketed_timer: self.bracketed_timer,
+            level_style: self.level_style,
+            theme: self.theme,
+            ansi: self.ansi,
+            #[cfg(feature = "ansi")]
+            target_colors: self.target_colors,
+            #[cfg(feature = "ansi")]
+            span_colors: self.span_colors,
+            #[cfg(feature = "ansi")]
+            file_links: self.file_links,
+            callsites: CallsiteCache::default(),
         }
     }
 
-    pub fn with_thread_names(self, with_thread_names: bool) -> Glog<T> {
+    /// Configures this formatter to produce the prefix of the given [`GlogDialect`].
+    ///
+    /// This replaces the timer with a [`UtcTime`] in the dialect's format, fills the id
+    /// column with the thread or process id as the reference implementation does and sets its
+    /// width, prints only the file's basename, and disables thread names and targets. Settings
+    /// made with [`Glog::with_thread_names`], [`Glog::with_target`] and
+    /// [`Glog::with_trimmed_directory`] are kept, whether they are made before or after this
+    /// call.
+    ///
+    /// Pair this with [`GlogFields::with_dialect`] so that fields are rendered the same way
+    /// as the reference implementation. To use local time instead, follow this with
+    /// `.with_timer(dialect.local_time())`.
+    pub fn with_dialect(self, dialect: GlogDialect) -> Glog<UtcTime> {
+        if !self.overridden.target {
+            self.switches.set(Switches::TARGET, false);
+        }
+        if !self.overridden.thread_names {
+            self.switches.set(Switches::THREAD_NAMES, false);
+        }
         Glog {
-            with_thread_names,
-            ..self
+            timer: dialect.utc_time(),
+            switches: self.switches,
+            with_prefix: self.with_prefix,
+            with_trimmed_directory: self.with_trimmed_directory
+                || !self.overridden.trimmed_directory,
+            strip_prefixes: self.strip_prefixes,
+            cargo_paths: self.cargo_paths,
+            target_abbreviation: self.target_abbreviation,
+            file_abbreviation: self.file_abbreviation,
+            columns: self.columns,
+            overridden: self.overridden,
+            pid_width: dialect.pid_width(),
+            with_thread_id: dialect.uses_thread_id(),
+            bracketed_timer: self.bracketed_timer,
+            level_style: self.level_style,
+            theme: self.theme,
+            ansi: self.ansi,
+            #[cfg(feature = "ansi")]
+            target_colors: self.target_colors,
+            #[cfg(feature = "ansi")]
+            span_colors: self.span_colors,
+            #[cfg(feature = "ansi")]
+            file_links: self.file_links,
+            callsites: CallsiteCache::default(),
         }
     }
 
-    pub fn with_target(self, with_target: bool) -> Glog<T> {
+    /// Sets whether or not the glog prefix (severity, time, process id and `file:line`) is
+    /// written, like glog's `--log_prefix`. Defaults to true.
+    ///
+    /// Without the prefix, lines start with the span context, followed by the event's fields.
+    pub fn with_prefix(self, with_prefix: bool) -> Glog<T> {
         Glog {
-            with_target,
+            with_prefix,
             ..self
         }
     }
 
-    pub fn with_trimmed_directory(self, with_trimmed_directory: bool) -> Glog<T> {
+    pub fn with_thread_names(mut self, with_thread_names: bool) -> Glog<T> {
+        self.switches.set(Switches::THREAD_NAMES, with_thread_names);
+        self.overridden.thread_names = true;
+        self
+    }
+
+    pub fn with_target(mut self, with_target: bool) -> Glog<T> {
+        self.switches.set(Switches::TARGET, with_target);
+        self.overridden.target = true;
+        self
+    }
+
+    pub fn with_trimmed_directory(mut self, with_trimmed_directory: bool) -> Glog<T> {
+        self.overridden.trimmed_directory = true;
         Glog {
             with_trimmed_directory,
             ..self
@@ -197,8 +354,186 @@ impl<T> Glog<T> {
     }
 
     pub fn with_strip_prefix<S: ToString>(self, with_strip_prefix: Option<S>) -> Glog<T> {
+        self.with_strip_prefixes(with_strip_prefix.map(|s| s.to_string()))
+    }
+
+    /// Removes a prefix from the paths of source files, such as the directory of a vendored
+    /// crate. If several prefixes match a path, the longest one is removed.
+    ///
+    /// This replaces any prefixes set by [`Glog::with_strip_prefix`]. Prefixes match whole
+    /// path components, so `/src/yak` does not match `/src/yaks/shave.rs`.
+    ///
+    /// ```
+    /// use tracing_glog::Glog;
+    ///
+    /// let format = Glog::default().with_strip_prefixes(["/build/vendor", "/build"]);
+    /// # drop(format);
+    /// ```
+    pub fn with_strip_prefixes<I>(self, prefixes: I) -> Glog<T>
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let mut strip_prefixes: Vec<String> = prefixes.into_iter().map(Into::into).collect();
+        strip_prefixes.sort_by_key(|prefix| std::cmp::Reverse(prefix.len()));
         Glog {
-            with_strip_prefix: with_strip_prefix.map(|s|s.to_string()),
+            strip_prefixes,
+            ..self
+        }
+    }
+
+    /// Shortens the paths of source files in Cargo's registry and in the current workspace.
+    ///
+    /// Paths of crates downloaded from a registry start at the crate's directory, so that
+    /// `~/.cargo/registry/src/index.crates.io-6f17d22bba15001f/tokio-1.38.0/src/runtime/mod.rs`
+    /// is shown as `tokio-1.38.0/src/runtime/mod.rs`. The registry is looked for in
+    /// `$CARGO_HOME`, or in `~/.cargo` if it is unset. Absolute paths in the current
+    /// workspace, such as those of path dependencies, are made relative to its root: the
+    /// closest directory containing a `Cargo.lock`, starting from `$CARGO_MANIFEST_DIR`
+    /// (which `cargo run` sets) or from the current directory.
+    ///
+    /// Prefixes set by [`Glog::with_strip_prefixes`] are tried first. Both directories are
+    /// looked up when this method is called.
+    pub fn with_cargo_paths(self, with_cargo_paths: bool) -> Glog<T> {
+        Glog {
+            cargo_paths: with_cargo_paths.then(CargoPaths::detect),
+            ..self
+        }
+    }
+
+    /// Abbreviates targets wider than `max_width` characters by shortening their leading
+    /// segments to one character, from left to right, like Logback's `%logger{n}`. The last
+    /// segment is always kept in full, so `my_company::storage::backend::s3::client` becomes
+    /// `m::s::b::s3::client` with a `max_width` of 20, and `m::s::b::s::client` with a smaller
+    /// one. `None`, the default, leaves targets as they are.
+    ///
+    /// Abbreviations are computed once per callsite. Target colors, set by
+    /// `Glog::with_target_colors`, are still picked from the full target.
+    ///
+    /// ```
+    /// use tracing_glog::Glog;
+    ///
+    /// let format = Glog::default()
+    ///     .with_target(true)
+    ///     .with_abbreviated_target(Some(24))
+    ///     .with_abbreviated_file(Some(24));
+    /// # drop(format);
+    /// ```
+    pub fn with_abbreviated_target(self, max_width: Option<usize>) -> Glog<T> {
+        Glog {
+            target_abbreviation: max_width,
+            ..self
+        }
+    }
+
+    /// Abbreviates the paths of source files that are wider than `max_width` characters in
+    /// the same way as [`Glog::with_abbreviated_target`], shortening leading directories so
+    /// that `src/runtime/scheduler/mod.rs` becomes `s/r/scheduler/mod.rs`. The file name is
+    /// always kept in full, and the line number is not counted. `None`, the default, leaves
+    /// paths as they are.
+    ///
+    /// Paths are abbreviated after being trimmed or stripped (see
+    /// [`Glog::with_trimmed_directory`] and [`Glog::with_strip_prefixes`]).
+    pub fn with_abbreviated_file(self, max_width: Option<usize>) -> Glog<T> {
+        Glog {
+            file_abbreviation: max_width,
+            ..self
+        }
+    }
+
+    /// Pads the thread name, target and `file:line` to the widths of `columns`, so that the
+    /// rest of each line starts in the same column. See [`Columns`], which also describes how
+    /// aligned lines remain parsable by glog parsers.
+    pub fn with_columns(self, columns: Columns) -> Glog<T> {
+        Glog { columns, ..self }
+    }
+
+    /// Records a second timer, such as [`Uptime`] or [`Monotonic`], in brackets right after
+    /// the glog prefix: `I1201 01:13:04.724801 1025672 lib.rs:34] [+0.001234s] message`.
+    ///
+    /// glog parsers treat the bracketed text as part of the message, so the prefix remains
+    /// parsable. The timer passed to [`Glog::with_timer`] is unaffected.
+    pub fn with_bracketed_timer<B>(self, timer: B) -> Glog<T>
+    where
+        B: FormatTime + Send + Sync + 'static,
+    {
+        Glog {
+            bracketed_timer: Some(Box::new(timer)),
+            ..self
+        }
+    }
+
+    /// Sets the styles of the timestamp, the callsite and the span context. See [`Theme`].
+    ///
+    /// Pass the same theme to [`GlogFields::with_theme`] to style field names.
+    pub fn with_theme(self, theme: Theme) -> Glog<T> {
+        Glog { theme, ..self }
+    }
+
+    /// Sets whether lines are styled, overriding the ANSI setting of the writer. See
+    /// [`AnsiMode`].
+    ///
+    /// The formatter does not know where its lines are written, so [`AnsiMode::Auto`] checks
+    /// `stream`, which should be the stream the writer writes to, when this method is called.
+    /// Pass the same mode and stream to [`GlogFields::with_ansi_mode`] so that fields are
+    /// styled consistently.
+    ///
+    /// ```
+    /// use tracing_glog::{AnsiMode, Glog, GlogFields};
+    ///
+    /// // `fmt()` writes to stdout by default.
+    /// let stdout = std::io::stdout();
+    /// tracing_subscriber::fmt()
+    ///     .event_format(Glog::default().with_ansi_mode(AnsiMode::Auto, &stdout))
+    ///     .fmt_fields(GlogFields::default().with_ansi_mode(AnsiMode::Auto, &stdout))
+    ///     .init();
+    /// ```
+    pub fn with_ansi_mode(self, ansi_mode: AnsiMode, stream: &impl IsTerminal) -> Glog<T> {
+        Glog {
+            ansi: Some(ansi_mode.is_enabled_for(stream)),
+            ..self
+        }
+    }
+
+    /// Colors each target with its own color, when targets are shown (see
+    /// [`Glog::with_target`]). See [`NameColors`].
+    ///
+    /// This method is available with the `ansi` feature.
+    #[cfg(feature = "ansi")]
+    pub fn with_target_colors(self, target_colors: NameColors) -> Glog<T> {
+        Glog {
+            target_colors: Some(target_colors),
+            ..self
+        }
+    }
+
+    /// Colors the name of the outermost span of the span context with its own color. See
+    /// [`NameColors`].
+    ///
+    /// This method is available with the `ansi` feature.
+    #[cfg(feature = "ansi")]
+    pub fn with_span_colors(self, span_colors: NameColors) -> Glog<T> {
+        Glog {
+            span_colors: Some(span_colors),
+            ..self
+        }
+    }
+
+    /// Makes each `file:line` a terminal hyperlink that opens the file. See [`FileLinks`].
+    ///
+    /// This method is available with the `ansi` feature.
+    #[cfg(feature = "ansi")]
+    pub fn with_file_links(self, file_links: FileLinks) -> Glog<T> {
+        Glog {
+            file_links: Some(file_links),
+            ..self
+        }
+    }
+
+    /// Sets the character and style of each level's severity. See [`LevelStyle`].
+    pub fn with_level_style(self, level_style: LevelStyle) -> Glog<T> {
+        Glog {
+            level_style,
             ..self
         }
     }
@@ -224,76 +559,93 @@ impl<T> Glog<T> {
     /// [`filter_fn`]: fn@tracing_subscriber::filter::filter_fn
     /// [`tracing`]: mod@tracing
     pub fn with_span_context(self, with_span_context: bool) -> Glog<T> {
-        Glog {
-            with_span_context,
-            ..self
-        }
+        self.switches.set(Switches::SPAN_CONTEXT, with_span_context);
+        self
     }
+
+    /// Returns a handle that changes the span context, target and thread name settings of
+    /// this formatter while it is in use, for instance from an admin endpoint or when the
+    /// process receives `SIGHUP`.
+    ///
+    /// See [`GlogHandle`].
+    pub fn reload_handle(&self) -> GlogHandle {
+        GlogHandle::new(self.switches.clone())
+    }
+}
+
+/// The settings of a [`Glog`] that were set explicitly, rather than left to their default or
+/// to a [`GlogDialect`].
+#[derive(Clone, Copy, Debug, Default)]
+struct Overridden {
+    thread_names: bool,
+    target: bool,
+    trimmed_directory: bool,
 }
 
 impl Default for Glog<UtcTime> {
     fn default() -> Self {
         Glog {
             timer: UtcTime::default(),
-            with_thread_names: false,
-            with_target: false,
-            with_span_context: true,
+            switches: Arc::new(Switches::new(Switches::SPAN_CONTEXT)),
+            with_prefix: true,
             with_trimmed_directory: false,
-            with_strip_prefix: None,
+            strip_prefixes: Vec::new(),
+            cargo_paths: None,
+            target_abbreviation: None,
+            file_abbreviation: None,
+            columns: Columns::default(),
+            overridden: Overridden::default(),
+            pid_width: GlogDialect::Glog.pid_width(),
+            with_thread_id: false,
+            bracketed_timer: None,
+            level_style: LevelStyle::default(),
+            theme: Theme::default(),
+            ansi: None,
+            #[cfg(feature = "ansi")]
+            target_colors: None,
+            #[cfg(feature = "ansi")]
+            span_colors: None,
+            #[cfg(feature = "ansi")]
+            file_links: None,
+            callsites: CallsiteCache::default(),
         }
     }
 }
 
-impl<S, N, T> FormatEvent<S, N> for Glog<T>
+impl<T> Glog<T>
 where
-    S: Subscriber + for<'a> LookupSpan<'a>,
-    N: for<'a> FormatFields<'a> + 'static,
     T: FormatTime,
 {
-    fn format_event(
+    /// Formats `event`, with `leaf` as the innermost span of its context.
+    ///
+    /// This is shared by the [`FormatEvent`] implementation and [`GlogLayer`]. `ansi` decides
+    /// whether styling is written, independently of `writer.has_ansi_escapes()`, and span
+    /// fields are read from the `F` extension of each span.
+    pub(crate) fn format_event_with<'a, R, F>(
         &self,
-        ctx: &FmtContext<'_, S, N>,
         mut writer: Writer<'_>,
         event: &tracing::Event<'_>,
-    ) -> fmt::Result {
-        let level = *event.metadata().level();
-
-        // Convert log level to a single character representation.)
-        let level = FmtLevel::format_level(level, writer.has_ansi_escapes());
-        write!(writer, "{}", level)?;
-
-        // write the timestamp:
-        self.timer.format_time(&mut writer)?;
-
-        // get some process information
-        let pid = get_pid();
-        let thread = std::thread::current();
-        let thread_name = thread.name();
-
-        #[cfg(feature = "tracing-log")]
-        let normalized_meta = event.normalized_metadata();
-        #[cfg(feature = "tracing-log")]
-        let metadata = normalized_meta.as_ref().unwrap_or_else(|| event.metadata());
-        #[cfg(not(feature = "tracing-log"))]
-        let metadata = event.metadata();
+        ansi: bool,
+        leaf: Option<SpanRef<'a, R>>,
+        format_fields: impl FnOnce(&mut Writer<'_>) -> fmt::Result,
+    ) -> fmt::Result
+    where
+        R: LookupSpan<'a>,
+        F: SpanFields,
+    {
+        let switches = self.switches.load();
+        if self.with_prefix {
+            self.format_prefix(&mut writer, event, ansi, switches)?;
+        }
 
-        let data = FormatProcessData {
-            pid,
-            thread_name,
-            with_thread_names: self.with_thread_names,
-            metadata,
-            with_target: self.with_target,
-            #[cfg(feature = "ansi")]
-            ansi: writer.has_ansi_escapes(),
-            with_trimmed_directory: self.with_trimmed_directory,
-            with_strip_prefix: &self.with_strip_prefix,
-        };
-        write!(writer, "{}] ", data)?;
+        if let Some(timer) = &self.bracketed_timer {
+            write!(writer, "[")?;
+            self.format_time(&**timer, &mut writer, ansi)?;
+            write!(writer, "] ")?;
+        }
 
-        if self.with_span_context {
+        if switches.span_context() {
             // now, we're printing the span context into brackets of `[]`, which glog parsers ignore.
-            let leaf = ctx.lookup_current();
-
             if let Some(leaf) = leaf {
                 // write the opening brackets
                 write!(writer, "[")?;
@@ -303,23 +655,27 @@ where
                 let mut span = iter.next().expect(
                     "Unable to get the next item in the iterator; this should not be possible.",
                 );
+                // Only the outermost span is colored.
+                #[cfg(feature = "ansi")]
+                let mut root_color = self
+                    .span_colors
+                    .as_ref()
+                    .filter(|_| ansi)
+                    .map(|colors| colors.color_for(span.name()));
                 loop {
                     let ext = span.extensions();
-                    let fields = &ext
-                        .get::<FormattedFields<N>>()
-                        .expect("will never be `None`");
+                    let fields = ext.get::<F>().map(F::as_str).unwrap_or_default();
 
                     let fields = if !fields.is_empty() {
-                        Some(fields.as_str())
+                        Some(fields)
                     } else {
                         None
                     };
 
-                    let fields = FormatSpanFields::format_fields(
-                        span.name(),
-                        fields,
-                        writer.has_ansi_escapes(),
-                    );
+                    let fields =
+                        FormatSpanFields::format_fields(span.name(), fields, ansi, &self.theme);
+                    #[cfg(feature = "ansi")]
+                    let fields = fields.with_name_color(root_color.take());
                     write!(writer, "{}", fields)?;
 
                     drop(ext);
@@ -336,20 +692,228 @@ where
                 write!(writer, "] ")?;
             }
         }
-        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        format_fields(&mut writer)?;
         writeln!(writer)
     }
+
+    /// Writes the glog prefix: severity, timestamp, process id, optional thread name, and
+    /// the callsite, up to and including the closing `] `.
+    fn format_prefix(
+        &self,
+        writer: &mut Writer<'_>,
+        event: &tracing::Event<'_>,
+        ansi: bool,
+        switches: Snapshot,
+    ) -> fmt::Result {
+        let level = *event.metadata().level();
+
+        // Convert log level to a single character representation.)
+        let level = FmtLevel::format_level(level, &self.level_style, ansi);
+        write!(writer, "{}", level)?;
+
+        // write the timestamp:
+        self.format_time(&self.timer, writer, ansi)?;
+
+        // get some process information
+        let pid = if self.with_thread_id {
+            get_thread_id()
+        } else {
+            get_pid()
+        };
+        let thread = std::thread::current();
+        let thread_name = thread.name();
+
+        #[cfg(feature = "tracing-log")]
+        let normalized_meta = event.normalized_metadata();
+        #[cfg(feature = "tracing-log")]
+        let metadata = normalized_meta.as_ref().unwrap_or_else(|| event.metadata());
+        #[cfg(not(feature = "tracing-log"))]
+        let metadata = event.metadata();
+
+        let format_callsite = FormatCallsite {
+            metadata,
+            with_trimmed_directory: self.with_trimmed_directory,
+            strip_prefixes: &self.strip_prefixes,
+            cargo_paths: self.cargo_paths.as_ref(),
+            target_abbreviation: self.target_abbreviation,
+            file_abbreviation: self.file_abbreviation,
+            columns: &self.columns,
+            #[cfg(feature = "ansi")]
+            target_colors: self
+                .target_colors
+                .as_ref()
+                .map(|colors| (colors, self.theme.callsite)),
+            #[cfg(feature = "ansi")]
+            file_links: self.file_links.as_ref(),
+        };
+        // Events from the `log` crate all share one callsite, so their prefix is rendered
+        // afresh rather than cached.
+        #[cfg(feature = "tracing-log")]
+        let callsite = if normalized_meta.is_some() {
+            std::sync::Arc::new(crate::format::CallsitePrefix::new(&format_callsite))
+        } else {
+            self.callsites.get_or_render(format_callsite)
+        };
+        #[cfg(not(feature = "tracing-log"))]
+        let callsite = self.callsites.get_or_render(format_callsite);
+
+        let data = FormatProcessData {
+            pid,
+            thread_name,
+            with_thread_names: switches.thread_names(),
+            with_target: switches.target(),
+            callsite: &callsite,
+            columns: &self.columns,
+            #[cfg(feature = "ansi")]
+            ansi,
+            #[cfg(feature = "ansi")]
+            style: self.theme.callsite,
+            pid_width: self.pid_width,
+        };
+        write!(writer, "{}] ", data)
+    }
+
+    /// Writes the time from `timer` in the theme's timestamp style.
+    fn format_time<F>(&self, timer: &F, writer: &mut Writer<'_>, ansi: bool) -> fmt::Result
+    where
+        F: FormatTime + ?Sized,
+    {
+        // Timers are given a plain writer, so that the theme alone decides how the timestamp
+        // is styled.
+        let mut plain = Writer::new(writer);
+        if ansi {
+            let style = self.theme.timestamp;
+            write!(plain, "{}", style.prefix())?;
+            timer.format_time(&mut plain)?;
+            return write!(plain, "{}", style.suffix());
+        }
+        timer.format_time(&mut plain)
+    }
+}
+
+impl<S, N, T> FormatEvent<S, N> for Glog<T>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+    T: FormatTime,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        writer: Writer<'_>,
+        event: &tracing::Event<'_>,
+    ) -> fmt::Result {
+        let ansi = self.ansi.unwrap_or_else(|| writer.has_ansi_escapes());
+        self.format_event_with::<_, FormattedFields<N>>(
+            writer,
+            event,
+            ansi,
+            ctx.lookup_current(),
+            |writer| ctx.field_format().format_fields(writer.by_ref(), event),
+        )
+    }
+}
+
+/// The rendered fields of a span, as stored in its extensions.
+pub(crate) trait SpanFields: 'static {
+    fn as_str(&self) -> &str;
+}
+
+impl<N: 'static> SpanFields for FormattedFields<N> {
+    fn as_str(&self) -> &str {
+        self.fields.as_str()
+    }
 }
 
 #[derive(Default)]
-pub struct GlogFields;
+pub struct GlogFields {
+    dialect: GlogDialect,
+    redacted_fields: Arc<[String]>,
+    theme: Theme,
+    /// Overrides whether the writer has ANSI escapes enabled.
+    ansi: Option<bool>,
+}
+
+impl GlogFields {
+    /// Returns a visitor that writes fields to `writer`, styled if `ansi` is true.
+    ///
+    /// If `is_empty` is false, the first field is preceded by a separator, so that fields can
+    /// be appended to previously rendered ones.
+    pub(crate) fn visitor<'a>(
+        &self,
+        writer: Writer<'a>,
+        ansi: bool,
+        is_empty: bool,
+    ) -> GlogVisitor<'a> {
+        GlogVisitor {
+            is_empty,
+            ansi,
+            ..self.make_visitor(writer)
+        }
+    }
+
+    /// Renders fields the way the given [`GlogDialect`] does.
+    ///
+    /// Only [`GlogDialect::Klog`] changes how fields are rendered: the message is quoted and
+    /// fields are written as space-separated `key=value` pairs.
+    pub fn with_dialect(self, dialect: GlogDialect) -> GlogFields {
+        GlogFields { dialect, ..self }
+    }
+
+    /// Sets whether fields are styled, overriding the ANSI setting of the writer, as with
+    /// [`Glog::with_ansi_mode`].
+    pub fn with_ansi_mode(self, ansi_mode: AnsiMode, stream: &impl IsTerminal) -> GlogFields {
+        GlogFields {
+            ansi: Some(ansi_mode.is_enabled_for(stream)),
+            ..self
+        }
+    }
+
+    /// Sets the style of field names, which is the [`Theme`]'s field name style.
+    ///
+    /// Pass the same theme to [`Glog::with_theme`] to style the rest of the line.
+    pub fn with_theme(self, theme: Theme) -> GlogFields {
+        GlogFields { theme, ..self }
+    }
+
+    /// Replaces the values of the fields with the given names by `<redacted>`, in both events
+    /// and spans.
+    ///
+    /// ```
+    /// use tracing_glog::{Glog, GlogFields};
+    ///
+    /// tracing_subscriber::fmt()
+    ///     .event_format(Glog::default())
+    ///     .fmt_fields(GlogFields::default().with_redacted_fields(["password", "token"]))
+    ///     .init();
+    ///
+    /// // I1201 01:13:04.724801 1025672 lib.rs:34] logging in, user: "yak", password: <redacted>
+    /// tracing::info!(user = "yak", password = "hunter2", "logging in");
+    /// ```
+    pub fn with_redacted_fields<I>(self, redacted_fields: I) -> GlogFields
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        GlogFields {
+            redacted_fields: redacted_fields.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+}
 
 impl<'a> MakeVisitor<Writer<'a>> for GlogFields {
     type Visitor = GlogVisitor<'a>;
 
     #[inline]
     fn make_visitor(&self, target: Writer<'a>) -> Self::Visitor {
-        GlogVisitor::new(target)
+        let ansi = self.ansi.unwrap_or_else(|| target.has_ansi_escapes());
+        GlogVisitor {
+            redacted_fields: self.redacted_fields.clone(),
+            field_name: self.theme.field_name,
+            ansi,
+            ..GlogVisitor::new(target, self.dialect)
+        }
     }
 }
 
@@ -357,54 +921,115 @@ impl<'a> MakeVisitor<Writer<'a>> for GlogFields {
 pub struct GlogVisitor<'a> {
     writer: Writer<'a>,
     is_empty: bool,
-    style: Style,
+    field_name: Style,
     result: fmt::Result,
+    dialect: GlogDialect,
+    ansi: bool,
+    redacted_fields: Arc<[String]>,
 }
 
 impl<'a> GlogVisitor<'a> {
-    fn new(writer: Writer<'a>) -> Self {
+    fn new(writer: Writer<'a>, dialect: GlogDialect) -> Self {
         Self {
+            ansi: writer.has_ansi_escapes(),
             writer,
             is_empty: true,
-            style: Style::new(),
+            field_name: Style::new(),
             result: Ok(()),
+            dialect,
+            redacted_fields: Arc::default(),
         }
     }
 
-    fn write_padded(&mut self, value: &impl fmt::Debug) {
-        let padding = if self.is_empty {
-            self.is_empty = false;
-            ""
-        } else {
-            ", "
-        };
-        self.result = write!(self.writer, "{}{:?}", padding, value);
-    }
+    /// Writes a field, preceded by a separator if it is not the first one.
+    ///
+    /// `name` is `None` for the message. Styling is only written when ANSI escapes are
+    /// enabled, so that the plain path never goes through `Style`.
+    fn write_field(&mut self, name: Option<&str>, value: &dyn fmt::Debug) -> fmt::Result {
+        let structured = self.dialect.is_structured();
+        if !self.is_empty {
+            self.writer.write_str(if structured { " " } else { ", " })?;
+        }
+        self.is_empty = false;
 
-    fn bold(&self) -> Style {
-        if self.writer.has_ansi_escapes() {
-            self.style.bold()
+        let name = match name {
+            Some(name) => name,
+            None if structured => return write!(self.writer, "{:?}", Quoted(value)),
+            None => return write!(self.writer, "{:?}", value),
+        };
+        if self.ansi {
+            let style = self.field_name;
+            write!(self.writer, "{}{}{}", style.prefix(), name, style.suffix())?;
         } else {
-            Style::new()
+            self.writer.write_str(name)?;
         }
+        self.writer.write_str(if structured { "=" } else { ": " })?;
+        write!(self.writer, "{:?}", value)
     }
 }
 
-impl<'a> Visit for GlogVisitor<'a> {
-    fn record_str(&mut self, field: &Field, value: &str) {
+impl<'a> GlogVisitor<'a> {
+    /// Records a field. With `quote`, the value is written in double quotes, as klog does
+    /// for values that are neither strings nor primitives.
+    fn record(&mut self, field: &Field, value: &dyn fmt::Debug, quote: bool) {
         if self.result.is_err() {
             return;
         }
 
+        let name = match field.name() {
+            "message" => None,
+            // Skip fields that are actually log metadata that have already been handled
+            name if name.starts_with("log.") => return,
+            name => Some(name.strip_prefix("r#").unwrap_or(name)),
+        };
+        if name.is_some_and(|name| self.redacted_fields.iter().any(|r| r == name)) {
+            self.result = self.write_field(name, &format_args!("<redacted>"));
+            return;
+        }
+        self.result = match name {
+            Some(_) if quote => self.write_field(name, &Quoted(value)),
+            _ => self.write_field(name, value),
+        };
+    }
+}
+
+impl<'a> Visit for GlogVisitor<'a> {
+    fn record_str(&mut self, field: &Field, value: &str) {
         if field.name() == "message" {
-            self.record_debug(field, &format_args!("{}", value))
+            self.record(field, &format_args!("{}", value), false)
         } else {
-            self.record_debug(field, &value)
+            self.record(field, &value, false)
         }
     }
 
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record(field, &value, false)
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record(field, &value, false)
+    }
+
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        self.record(field, &value, false)
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        self.record(field, &value, false)
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.record(field, &value, false)
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.record(field, &value, false)
+    }
+
     fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
-        if let Some(source) = value.source() {
+        if self.dialect.is_structured() {
+            self.record_debug(field, &format_args!("{}", value))
+        } else if let Some(source) = value.source() {
             self.record_debug(
                 field,
                 &format_args!("{}, {}.sources: {}", value, field, ErrorSourceList(source),),
@@ -415,36 +1040,12 @@ impl<'a> Visit for GlogVisitor<'a> {
     }
 
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
-        if self.result.is_err() {
-            return;
-        }
-
-        let bold = self.bold();
-        match field.name() {
-            "message" => self.write_padded(&format_args!("{}{:?}", self.style.prefix(), value,)),
-            // Skip fields that are actually log metadata that have already been handled
-            name if name.starts_with("log.") => self.result = Ok(()),
-            name if name.starts_with("r#") => self.write_padded(&format_args!(
-                "{}{}{}: {:?}",
-                bold.prefix(),
-                &name[2..],
-                bold.infix(self.style),
-                value
-            )),
-            name => self.write_padded(&format_args!(
-                "{}{}{}: {:?}",
-                bold.prefix(),
-                name,
-                bold.infix(self.style),
-                value
-            )),
-        };
+        self.record(field, value, self.dialect.is_structured())
     }
 }
 
 impl<'a> VisitOutput<fmt::Result> for GlogVisitor<'a> {
-    fn finish(mut self) -> fmt::Result {
-        write!(&mut self.writer, "{}", self.style.suffix())?;
+    fn finish(self) -> fmt::Result {
         self.result
     }
 }
@@ -474,3 +1075,81 @@ impl<'a> fmt::Display for ErrorSourceList<'a> {
 fn get_pid() -> u32 {
     std::process::id()
 }
+
+/// Returns the id of the current thread, as assigned by the OS. On platforms where it is not
+/// available, this returns the process id.
+#[inline(always)]
+fn get_thread_id() -> u32 {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        // SAFETY: `gettid` cannot fail.
+        unsafe { libc::gettid() as u32 }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    get_pid()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::format_with;
+
+    /// Formats the events logged by `log` with `fields`, without a prefix.
+    fn fields_of(fields: GlogFields, log: impl FnOnce()) -> String {
+        format_with(Glog::default().with_prefix(false), fields, false, log)
+    }
+
+    /// Formats an event with `format`, and returns its line after the thread id.
+    fn callsite_of(format: Glog) -> String {
+        let output = format_with(format, GlogFields::default(), false, || {
+            tracing::info!("hello")
+        });
+        let mut rest = output.as_str();
+        for _ in 0..3 {
+            rest = rest.trim_start();
+            rest = &rest[rest.find(' ').unwrap()..];
+        }
+        rest[1..].to_string()
+    }
+
+    #[test]
+    fn dialect_keeps_explicit_settings() {
+        let target = "[tracing_glog::tests] ";
+        let thread = std::thread::current().name().unwrap().to_string();
+
+        let dialect_only = callsite_of(Glog::default().with_dialect(GlogDialect::Glog));
+        assert!(dialect_only.starts_with("lib.rs:"), "{}", dialect_only);
+
+        // Settings made before or after the dialect are kept either way.
+        let before = Glog::default()
+            .with_target(true)
+            .with_thread_names(true)
+            .with_trimmed_directory(false)
+            .with_dialect(GlogDialect::Glog);
+        let after = Glog::default()
+            .with_dialect(GlogDialect::Glog)
+            .with_target(true)
+            .with_thread_names(true)
+            .with_trimmed_directory(false);
+        for format in [before, after] {
+            let line = callsite_of(format);
+            let expected = format!("{} {}src/lib.rs:", thread, target);
+            assert!(line.starts_with(&expected), "{:?}", line);
+        }
+
+        // Explicitly disabling a setting that the dialect enables is kept as well.
+        let format = Glog::default()
+            .with_trimmed_directory(false)
+            .with_dialect(GlogDialect::Glog);
+        assert!(callsite_of(format).starts_with("src/lib.rs:"));
+    }
+
+    #[test]
+    fn klog_skips_log_metadata() {
+        let fields = GlogFields::default().with_dialect(GlogDialect::Klog);
+        let output = fields_of(fields, || {
+            tracing::info!(log.target = "yaks", log.line = 3, yak = 1, "shaving")
+        });
+        assert_eq!(output, "\"shaving\" yak=1\n");
+    }
+}
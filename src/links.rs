@@ -0,0 +1,244 @@
+use std::{
+    fmt::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Turns the `file:line` of each line into a terminal hyperlink, using the [OSC 8] escape
+/// sequence that most modern terminals support.
+///
+/// By default, links open the file with a `file://` URL of its absolute path. With
+/// [`FileLinks::with_template`], they open it with another URL, such as one that opens the
+/// file at the right line in an editor. Either way, the link's text is the `file:line` that
+/// would have been written without it, including the effects of
+/// [`Glog::with_trimmed_directory`] and [`Glog::with_strip_prefix`]. Links are only written
+/// when ANSI escapes are enabled.
+///
+/// Use [`Glog::with_file_links`] to enable links. This type is available with the `ansi`
+/// feature.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_glog::{FileLinks, Glog, GlogFields};
+///
+/// let links = FileLinks::new().with_template("vscode://file/{path}:{line}");
+/// tracing_subscriber::fmt()
+///     .event_format(Glog::default().with_file_links(links))
+///     .fmt_fields(GlogFields::default())
+///     .init();
+/// ```
+///
+/// [OSC 8]: https://gist.github.com/egmontkob/eb114294efbcd5adb1944c9f3cb5feda
+/// [`Glog::with_trimmed_directory`]: crate::Glog::with_trimmed_directory
+/// [`Glog::with_strip_prefix`]: crate::Glog::with_strip_prefix
+/// [`Glog::with_file_links`]: crate::Glog::with_file_links
+#[derive(Clone, Debug)]
+pub struct FileLinks {
+    template: Option<String>,
+    base_dir: Option<PathBuf>,
+}
+
+impl FileLinks {
+    /// Returns links to `file://` URLs, with relative paths resolved against the current
+    /// directory.
+    pub fn new() -> Self {
+        FileLinks {
+            template: None,
+            base_dir: std::env::current_dir().ok(),
+        }
+    }
+
+    /// Builds links from a URL template, in which `{path}` is replaced by the absolute path of
+    /// the file and `{line}` by the line number, such as `vscode://file/{path}:{line}` or
+    /// `idea://open?file={path}&line={line}`.
+    pub fn with_template(self, template: impl Into<String>) -> Self {
+        FileLinks {
+            template: Some(template.into()),
+            ..self
+        }
+    }
+
+    /// Sets the directory that relative paths are resolved against. Defaults to the current
+    /// directory when the links were created.
+    ///
+    /// Paths recorded by the compiler are relative to the workspace that a crate was built
+    /// in, so set this if the program runs from another directory.
+    pub fn with_base_dir(self, base_dir: impl Into<PathBuf>) -> Self {
+        FileLinks {
+            base_dir: Some(base_dir.into()),
+            ..self
+        }
+    }
+
+    /// Returns the URL of `line` in `file`, as recorded in an event's metadata.
+    pub(crate) fn url(&self, file: &str, line: Option<u32>) -> String {
+        let path = match &self.base_dir {
+            Some(base_dir) if !is_absolute(file) => base_dir.join(file),
+            _ => PathBuf::from(file),
+        };
+        let path = PercentEncoded(&path.to_string_lossy()).to_string();
+        let line = line.map(|line| line.to_string()).unwrap_or_default();
+        match &self.template {
+            Some(template) => template.replace("{path}", &path).replace("{line}", &line),
+            None if path.starts_with('/') => format!("file://{}", path),
+            None => format!("file:///{}", path),
+        }
+    }
+}
+
+/// Returns whether `file` is an absolute path, on this platform or as written by a compiler
+/// on Windows, such as `C:\src\main.rs`.
+fn is_absolute(file: &str) -> bool {
+    let drive = file.as_bytes();
+    Path::new(file).is_absolute()
+        || matches!(drive, [letter, b':', b'\\' | b'/', ..] if letter.is_ascii_alphabetic())
+}
+
+impl Default for FileLinks {
+    fn default() -> Self {
+        FileLinks::new()
+    }
+}
+
+/// Writes `text` as an OSC 8 hyperlink to `url`.
+pub(crate) struct Hyperlink<'a, T> {
+    pub(crate) url: &'a str,
+    pub(crate) text: T,
+}
+
+impl<'a, T: fmt::Display> fmt::Display for Hyperlink<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", self.url, self.text)
+    }
+}
+
+/// Percent-encodes the characters of a path that are not allowed in a URL, as well as the
+/// control characters that would end the escape sequence.
+struct PercentEncoded<'a>(&'a str);
+
+impl<'a> fmt::Display for PercentEncoded<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                    f.write_char(byte as char)?
+                }
+                // Windows paths start with a drive letter, and use backslashes.
+                b':' => f.write_char(':')?,
+                b'\\' => f.write_char('/')?,
+                _ => write!(f, "%{:02X}", byte)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::format_with, Glog, GlogFields};
+
+    fn links() -> FileLinks {
+        FileLinks::new().with_base_dir("/work/yaks")
+    }
+
+    #[test]
+    fn urls_are_percent_encoded() {
+        let url = |file| links().url(file, Some(1));
+        assert_eq!(url("/src/my file.rs"), "file:///src/my%20file.rs");
+        assert_eq!(url("/src/100%.rs"), "file:///src/100%25.rs");
+        assert_eq!(url("/src/yäk.rs"), "file:///src/y%C3%A4k.rs");
+        assert_eq!(url("/src/a-b_c~d.rs"), "file:///src/a-b_c~d.rs");
+        // Characters that would end the escape sequence early.
+        assert_eq!(url("/src/\x1b\x07.rs"), "file:///src/%1B%07.rs");
+        assert_eq!(url("/src/#?&=.rs"), "file:///src/%23%3F%26%3D.rs");
+    }
+
+    #[test]
+    fn relative_paths_are_resolved_against_the_base_dir() {
+        assert_eq!(
+            links().url("src/main.rs", Some(1)),
+            "file:///work/yaks/src/main.rs"
+        );
+        assert_eq!(
+            links().url("/usr/src/main.rs", Some(1)),
+            "file:///usr/src/main.rs"
+        );
+        let current_dir = std::env::current_dir().unwrap();
+        assert_eq!(
+            FileLinks::new().url("src/main.rs", None),
+            FileLinks::new()
+                .with_base_dir(current_dir)
+                .url("src/main.rs", None)
+        );
+    }
+
+    #[test]
+    fn windows_paths() {
+        assert_eq!(
+            links().url("C:\\Users\\yak\\src\\main.rs", Some(1)),
+            "file:///C:/Users/yak/src/main.rs"
+        );
+        assert_eq!(
+            links().url("d:/src/main.rs", Some(1)),
+            "file:///d:/src/main.rs"
+        );
+        assert_eq!(
+            links().url("src\\main.rs", Some(1)),
+            "file:///work/yaks/src/main.rs"
+        );
+    }
+
+    #[test]
+    fn templates() {
+        let vscode = links().with_template("vscode://file/{path}:{line}");
+        assert_eq!(
+            vscode.url("src/main.rs", Some(42)),
+            "vscode://file//work/yaks/src/main.rs:42"
+        );
+        assert_eq!(
+            vscode.url("src/main.rs", None),
+            "vscode://file//work/yaks/src/main.rs:"
+        );
+        let idea = links().with_template("idea://open?file={path}&line={line}&again={path}");
+        assert_eq!(
+            idea.url("/a b.rs", Some(7)),
+            "idea://open?file=/a%20b.rs&line=7&again=/a%20b.rs"
+        );
+    }
+
+    #[test]
+    fn linked_lines_keep_their_text() {
+        let glog = || {
+            Glog::default()
+                .with_trimmed_directory(false)
+                .with_strip_prefix(Some("src/"))
+                .with_file_links(links())
+        };
+        let log = || {
+            let line = line!() + 1;
+            tracing::info!("hello");
+            line
+        };
+
+        let mut line = 0;
+        let plain = format_with(glog(), GlogFields::default(), false, || line = log());
+        assert!(
+            plain.ends_with(&format!(" links.rs:{}] hello\n", line)),
+            "{:?}",
+            plain
+        );
+        assert!(!plain.contains('\x1b'));
+
+        let linked = format_with(glog(), GlogFields::default(), true, || line = log());
+        let url = "file:///work/yaks/src/links.rs";
+        assert!(
+            linked.contains(&format!(
+                "\x1b]8;;{}\x1b\\links.rs:{}\x1b]8;;\x1b\\",
+                url, line
+            )),
+            "{:?}",
+            linked
+        );
+    }
+}
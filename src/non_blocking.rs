@@ -0,0 +1,509 @@
+use crate::{
+    shutdown::{self, Flush, Stage},
+    GlogDialect,
+};
+use std::{
+    fmt::Write as _,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+        Arc, Mutex, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use time::OffsetDateTime;
+use tracing::Level;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// What a [`NonBlocking`] writer does when its queue is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BackPressure {
+    /// Drop the line and count it. The number of dropped lines is reported periodically as a
+    /// `W` record in glog format. This is the default.
+    #[default]
+    Lossy,
+    /// Block the logging thread until there is room in the queue.
+    Block,
+}
+
+/// A writer that hands formatted lines to a background thread through a bounded queue.
+///
+/// Writing to stderr or a file synchronously puts the cost of the write system call (and any
+/// contention on the file) on the request path. `NonBlocking` instead queues each line for a
+/// background thread, which writes it to the wrapped writer. When the queue is full, lines
+/// are either dropped or the logging thread blocks, depending on the [`BackPressure`] mode.
+///
+/// Dropped lines are counted, and the background thread periodically writes a record such as
+/// `W1201 01:13:04.724801  4242 non_blocking.rs:312] dropped 17 log lines` so that gaps in the
+/// log are visible. The writer does not know how the lines it carries were formatted, so this
+/// record always has the default glog layout, with a UTC timestamp and the process id,
+/// whatever timer and dialect the formatter uses.
+///
+/// Creating a `NonBlocking` writer also returns a [`WorkerGuard`]. Dropping the guard writes
+/// every queued line, flushes the wrapped writer and stops the background thread, so it must
+/// be held until the end of `main`. [`flush`] and [`shutdown`] also reach every
+/// `NonBlocking` writer.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_glog::{Glog, GlogFields, NonBlocking};
+///
+/// let (writer, _guard) = NonBlocking::new(std::io::stderr());
+/// tracing_subscriber::fmt()
+///     .with_writer(writer)
+///     .event_format(Glog::default())
+///     .fmt_fields(GlogFields::default())
+///     .init();
+/// ```
+///
+/// [`flush`]: crate::flush
+/// [`shutdown`]: crate::shutdown
+#[derive(Clone, Debug)]
+pub struct NonBlocking {
+    sender: SyncSender<Msg>,
+    dropped: Arc<AtomicU64>,
+    back_pressure: BackPressure,
+}
+
+/// Configures a [`NonBlocking`] writer.
+#[derive(Clone, Debug)]
+pub struct NonBlockingBuilder {
+    queue_len: usize,
+    back_pressure: BackPressure,
+    report_interval: Duration,
+    thread_name: String,
+}
+
+/// Writes every queued line and stops the background thread of a [`NonBlocking`] writer when
+/// dropped.
+#[must_use = "dropping the guard immediately stops the background writer"]
+#[derive(Debug)]
+pub struct WorkerGuard {
+    worker: Arc<WorkerHandle>,
+}
+
+/// The sending end of a background writer, through which it is flushed and shut down.
+#[derive(Debug)]
+struct WorkerHandle {
+    sender: SyncSender<Msg>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+#[derive(Debug)]
+enum Msg {
+    Line(Vec<u8>),
+    /// Write everything queued so far and flush the writer, then acknowledge.
+    Flush(SyncSender<()>),
+    Shutdown,
+}
+
+impl NonBlocking {
+    /// Returns a non-blocking writer that writes to `writer` on a background thread, with the
+    /// default configuration of [`NonBlocking::builder`].
+    pub fn new<W>(writer: W) -> (NonBlocking, WorkerGuard)
+    where
+        W: Write + Send + 'static,
+    {
+        NonBlocking::builder().finish(writer)
+    }
+
+    /// Returns a builder for a non-blocking writer.
+    ///
+    /// By default, up to 128,000 lines are queued, lines are dropped when the queue is full,
+    /// and dropped lines are reported at most once per second.
+    pub fn builder() -> NonBlockingBuilder {
+        NonBlockingBuilder::default()
+    }
+
+    /// Returns the number of lines that have been dropped and not yet reported.
+    pub fn dropped_lines(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn send(&self, line: Vec<u8>) {
+        match self.back_pressure {
+            BackPressure::Lossy => match self.sender.try_send(Msg::Line(line)) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                // The worker has shut down; there is nowhere left to write to.
+                Err(TrySendError::Disconnected(_)) => {}
+            },
+            BackPressure::Block => {
+                let _ = self.sender.send(Msg::Line(line));
+            }
+        }
+    }
+}
+
+impl Default for NonBlockingBuilder {
+    fn default() -> Self {
+        NonBlockingBuilder {
+            queue_len: 128_000,
+            back_pressure: BackPressure::Lossy,
+            report_interval: Duration::from_secs(1),
+            thread_name: "tracing-glog-writer".to_string(),
+        }
+    }
+}
+
+impl NonBlockingBuilder {
+    /// Sets the maximum number of lines waiting to be written.
+    pub fn queue_len(self, queue_len: usize) -> Self {
+        NonBlockingBuilder { queue_len, ..self }
+    }
+
+    /// Sets what happens when the queue is full.
+    pub fn back_pressure(self, back_pressure: BackPressure) -> Self {
+        NonBlockingBuilder {
+            back_pressure,
+            ..self
+        }
+    }
+
+    /// Sets how often dropped lines are reported.
+    pub fn report_interval(self, report_interval: Duration) -> Self {
+        NonBlockingBuilder {
+            report_interval,
+            ..self
+        }
+    }
+
+    /// Sets the name of the background thread.
+    pub fn thread_name(self, thread_name: impl Into<String>) -> Self {
+        NonBlockingBuilder {
+            thread_name: thread_name.into(),
+            ..self
+        }
+    }
+
+    /// Starts the background thread writing to `writer`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the background thread cannot be spawned.
+    pub fn finish<W>(self, writer: W) -> (NonBlocking, WorkerGuard)
+    where
+        W: Write + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(self.queue_len);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let worker = Worker {
+            writer: BufWriter::new(writer),
+            receiver,
+            dropped: dropped.clone(),
+            report_interval: self.report_interval,
+        };
+        let handle = thread::Builder::new()
+            .name(self.thread_name)
+            .spawn(move || worker.run())
+            .expect("Unable to spawn the tracing-glog writer thread");
+
+        let non_blocking = NonBlocking {
+            sender: sender.clone(),
+            dropped,
+            back_pressure: self.back_pressure,
+        };
+        let worker = Arc::new(WorkerHandle {
+            sender,
+            thread: Mutex::new(Some(handle)),
+        });
+        let weak = Arc::downgrade(&worker);
+        shutdown::register(Stage::Queue, weak);
+        (non_blocking, WorkerGuard { worker })
+    }
+}
+
+impl Write for NonBlocking {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.send(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    /// Waits until every line queued so far has been written and the wrapped writer has been
+    /// flushed.
+    fn flush(&mut self) -> io::Result<()> {
+        flush_worker(&self.sender);
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for NonBlocking {
+    type Writer = NonBlocking;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+impl Drop for WorkerGuard {
+    fn drop(&mut self) {
+        self.worker.shutdown();
+    }
+}
+
+impl Flush for WorkerHandle {
+    fn flush(&self, _: Level) {
+        // Lines of all levels share the queue, so they are all written out.
+        flush_worker(&self.sender);
+    }
+
+    fn shutdown(&self) {
+        let thread = self
+            .thread
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(thread) = thread {
+            // The worker drains the queue before it sees this message.
+            let _ = self.sender.send(Msg::Shutdown);
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Waits until the worker has written every line queued so far and flushed its writer.
+fn flush_worker(sender: &SyncSender<Msg>) {
+    let (ack, done) = mpsc::sync_channel(1);
+    if sender.send(Msg::Flush(ack)).is_ok() {
+        // If the worker stops first, `ack` is dropped and this returns an error.
+        let _ = done.recv();
+    }
+}
+
+struct Worker<W: Write> {
+    writer: BufWriter<W>,
+    receiver: Receiver<Msg>,
+    dropped: Arc<AtomicU64>,
+    report_interval: Duration,
+}
+
+impl<W: Write> Worker<W> {
+    fn run(mut self) {
+        let mut last_report = Instant::now();
+        'run: loop {
+            let mut msg = match self.receiver.recv_timeout(self.report_interval) {
+                Ok(msg) => Some(msg),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            // Handle everything that is already queued before flushing, so that bursts are
+            // written in large chunks.
+            while let Some(next) = msg {
+                if !self.handle(next) {
+                    break 'run;
+                }
+                msg = self.receiver.try_recv().ok();
+            }
+
+            if last_report.elapsed() >= self.report_interval {
+                self.report_dropped();
+                last_report = Instant::now();
+            }
+            let _ = self.writer.flush();
+        }
+
+        self.report_dropped();
+        let _ = self.writer.flush();
+    }
+
+    /// Handles one message, returning false once the worker should stop.
+    fn handle(&mut self, msg: Msg) -> bool {
+        match msg {
+            Msg::Line(line) => {
+                let _ = self.writer.write_all(&line);
+            }
+            Msg::Flush(ack) => {
+                self.report_dropped();
+                let _ = self.writer.flush();
+                let _ = ack.send(());
+            }
+            Msg::Shutdown => return false,
+        }
+        true
+    }
+
+    fn report_dropped(&mut self) {
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            let _ = self
+                .writer
+                .write_all(dropped_lines_record(dropped).as_bytes());
+        }
+    }
+}
+
+/// Renders the glog record reporting that `dropped` lines were lost.
+fn dropped_lines_record(dropped: u64) -> String {
+    let now = OffsetDateTime::now_utc();
+    let timestamp = now
+        .format(&GlogDialect::Glog.time_format())
+        .unwrap_or_default();
+    let file = Path::new(file!())
+        .file_name()
+        .and_then(|f| f.to_str())
+        .unwrap_or_default();
+    let mut record = String::new();
+    let _ = writeln!(
+        record,
+        "W{} {:>5} {}:{}] dropped {} log lines",
+        timestamp,
+        std::process::id(),
+        file,
+        line!(),
+        dropped
+    );
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Capture;
+    use std::sync::mpsc::Sender;
+
+    /// A writer that signals every write on `entered`, then waits until `gate` receives a
+    /// message or is disconnected.
+    struct Gated {
+        entered: Sender<()>,
+        gate: Receiver<()>,
+        out: Capture,
+    }
+
+    impl Write for Gated {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let _ = self.entered.send(());
+            let _ = self.gate.recv();
+            self.out.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Returns a writer that blocks until the returned sender is dropped, a receiver of its
+    /// write attempts, and its output.
+    fn gated() -> (Gated, SyncSender<()>, Receiver<()>, Capture) {
+        let (entered, entered_rx) = mpsc::channel();
+        let (gate_tx, gate) = mpsc::sync_channel(0);
+        let out = Capture::default();
+        let writer = Gated {
+            entered,
+            gate,
+            out: out.clone(),
+        };
+        (writer, gate_tx, entered_rx, out)
+    }
+
+    fn builder(queue_len: usize, back_pressure: BackPressure) -> NonBlockingBuilder {
+        NonBlocking::builder()
+            .queue_len(queue_len)
+            .back_pressure(back_pressure)
+            // Dropped lines are only reported when the writer is flushed or shut down.
+            .report_interval(Duration::from_secs(3600))
+    }
+
+    #[test]
+    fn lossy_mode_reports_dropped_lines() {
+        let (writer, gate, entered, out) = gated();
+        let (mut non_blocking, guard) = builder(2, BackPressure::Lossy).finish(writer);
+
+        // The worker blocks writing the first line, so the queue fills up behind it.
+        non_blocking.write_all(b"a\n").unwrap();
+        entered.recv().unwrap();
+        for line in ["b\n", "c\n", "d\n", "e\n", "f\n"] {
+            non_blocking.write_all(line.as_bytes()).unwrap();
+        }
+        assert_eq!(non_blocking.dropped_lines(), 3);
+
+        drop(gate);
+        drop(guard);
+        let contents = out.contents();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[..3], ["a", "b", "c"], "{}", contents);
+        assert_eq!(lines.len(), 4, "{}", contents);
+        assert!(lines[3].starts_with('W'), "{}", lines[3]);
+        assert!(lines[3].ends_with("] dropped 3 log lines"), "{}", lines[3]);
+        assert_eq!(non_blocking.dropped_lines(), 0);
+    }
+
+    #[test]
+    fn block_mode_loses_nothing() {
+        let (writer, gate, entered, out) = gated();
+        let (non_blocking, guard) = builder(1, BackPressure::Block).finish(writer);
+
+        let sender = thread::spawn({
+            let mut non_blocking = non_blocking.clone();
+            move || {
+                for i in 0..50 {
+                    non_blocking
+                        .write_all(format!("{}\n", i).as_bytes())
+                        .unwrap();
+                }
+            }
+        });
+        entered.recv().unwrap();
+        // The sender is stuck behind the full queue until the writer is released.
+        thread::sleep(Duration::from_millis(20));
+        assert!(!sender.is_finished());
+
+        drop(gate);
+        sender.join().unwrap();
+        drop(guard);
+        let expected: String = (0..50).map(|i| format!("{}\n", i)).collect();
+        assert_eq!(out.contents(), expected);
+        assert_eq!(non_blocking.dropped_lines(), 0);
+    }
+
+    #[test]
+    fn dropping_the_guard_drains_the_queue() {
+        let out = Capture::default();
+        let (mut non_blocking, guard) =
+            builder(1024, BackPressure::Lossy).finish(io::LineWriter::new(out.clone()));
+        for i in 0..500 {
+            non_blocking
+                .write_all(format!("{}\n", i).as_bytes())
+                .unwrap();
+        }
+        drop(guard);
+
+        let expected: String = (0..500).map(|i| format!("{}\n", i)).collect();
+        assert_eq!(out.contents(), expected);
+        // Lines written after the worker stopped go nowhere.
+        non_blocking.write_all(b"late\n").unwrap();
+        non_blocking.flush().unwrap();
+        assert_eq!(out.contents(), expected);
+    }
+
+    #[test]
+    fn flush_waits_for_queued_lines() {
+        let out = Capture::default();
+        let (mut non_blocking, _guard) = builder(1024, BackPressure::Lossy).finish(out.clone());
+        for i in 0..100 {
+            non_blocking
+                .write_all(format!("{}\n", i).as_bytes())
+                .unwrap();
+        }
+        non_blocking.flush().unwrap();
+        assert_eq!(out.contents().lines().count(), 100);
+    }
+
+    #[test]
+    fn dropped_lines_records_have_the_glog_layout() {
+        let record = dropped_lines_record(17);
+        let (prefix, message) = record.split_once("] ").unwrap();
+        assert_eq!(message, "dropped 17 log lines\n");
+        let mut fields = prefix.split_whitespace();
+        let date = fields.next().unwrap();
+        assert!(date.starts_with('W') && date.len() == 5, "{}", record);
+        assert_eq!(fields.next().unwrap().len(), "hh:mm:ss.uuuuuu".len());
+        assert_eq!(fields.next().unwrap(), std::process::id().to_string());
+        assert!(fields.next().unwrap().starts_with("non_blocking.rs:"));
+    }
+}
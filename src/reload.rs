@@ -0,0 +1,232 @@
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+};
+
+/// The settings of a [`Glog`] that can be changed while it is in use, packed into one atomic
+/// so that an event reads all of them with a single load.
+///
+/// [`Glog`]: crate::Glog
+#[derive(Debug)]
+pub(crate) struct Switches(AtomicU8);
+
+/// The value of [`Switches`] at one point in time.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Snapshot(u8);
+
+impl Switches {
+    pub(crate) const SPAN_CONTEXT: u8 = 1 << 0;
+    pub(crate) const TARGET: u8 = 1 << 1;
+    pub(crate) const THREAD_NAMES: u8 = 1 << 2;
+
+    pub(crate) fn new(switches: u8) -> Self {
+        Switches(AtomicU8::new(switches))
+    }
+
+    pub(crate) fn load(&self) -> Snapshot {
+        Snapshot(self.0.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn set(&self, switch: u8, on: bool) {
+        if on {
+            self.0.fetch_or(switch, Ordering::Relaxed);
+        } else {
+            self.0.fetch_and(!switch, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Snapshot {
+    pub(crate) fn span_context(self) -> bool {
+        self.0 & Switches::SPAN_CONTEXT != 0
+    }
+
+    pub(crate) fn target(self) -> bool {
+        self.0 & Switches::TARGET != 0
+    }
+
+    pub(crate) fn thread_names(self) -> bool {
+        self.0 & Switches::THREAD_NAMES != 0
+    }
+}
+
+/// Changes the settings of a [`Glog`] formatter while it is in use.
+///
+/// A handle is returned by [`Glog::reload_handle`], and stays connected to the formatter
+/// after it has been installed in a subscriber. Handles are cheap to clone and can be sent
+/// to another thread, such as one serving an admin endpoint or waiting for `SIGHUP`.
+///
+/// Each change takes effect atomically from the next event on: an event is formatted either
+/// entirely with the old setting or entirely with the new one. Formatting an event reads
+/// the settings with a single atomic load, without taking a lock.
+///
+/// To change the verbosity and `vmodule` patterns, use a [`GlogFilterHandle`].
+///
+/// # Examples
+///
+/// ```
+/// use tracing_glog::{Glog, GlogFields};
+///
+/// let glog = Glog::default();
+/// let handle = glog.reload_handle();
+/// tracing_subscriber::fmt()
+///     .event_format(glog)
+///     .fmt_fields(GlogFields::default())
+///     .init();
+///
+/// tracing::info!("without a target");
+/// handle.set_target(true);
+/// tracing::info!("with a target");
+/// assert!(handle.target());
+/// ```
+///
+/// [`Glog`]: crate::Glog
+/// [`Glog::reload_handle`]: crate::Glog::reload_handle
+/// [`GlogFilterHandle`]: crate::GlogFilterHandle
+#[derive(Clone, Debug)]
+pub struct GlogHandle {
+    switches: Arc<Switches>,
+}
+
+impl GlogHandle {
+    pub(crate) fn new(switches: Arc<Switches>) -> Self {
+        GlogHandle { switches }
+    }
+
+    /// Sets whether the span context is written, as with [`Glog::with_span_context`].
+    ///
+    /// [`Glog::with_span_context`]: crate::Glog::with_span_context
+    pub fn set_span_context(&self, with_span_context: bool) {
+        self.switches.set(Switches::SPAN_CONTEXT, with_span_context);
+    }
+
+    /// Sets whether the target is written, as with [`Glog::with_target`].
+    ///
+    /// [`Glog::with_target`]: crate::Glog::with_target
+    pub fn set_target(&self, with_target: bool) {
+        self.switches.set(Switches::TARGET, with_target);
+    }
+
+    /// Sets whether thread names are written, as with [`Glog::with_thread_names`].
+    ///
+    /// [`Glog::with_thread_names`]: crate::Glog::with_thread_names
+    pub fn set_thread_names(&self, with_thread_names: bool) {
+        self.switches.set(Switches::THREAD_NAMES, with_thread_names);
+    }
+
+    /// Returns whether the span context is written.
+    pub fn span_context(&self) -> bool {
+        self.switches.load().span_context()
+    }
+
+    /// Returns whether the target is written.
+    pub fn target(&self) -> bool {
+        self.switches.load().target()
+    }
+
+    /// Returns whether thread names are written.
+    pub fn thread_names(&self) -> bool {
+        self.switches.load().thread_names()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::format_with, Glog, GlogFields, GlogOptions};
+    use tracing_subscriber::prelude::*;
+
+    /// Returns the lines of `output` after the thread id, with the line number of each event
+    /// replaced by `N`.
+    fn after_thread_id(output: &str) -> Vec<String> {
+        output
+            .lines()
+            .map(|line| {
+                // Skip the level and date, the time and the right-aligned id.
+                let rest = line
+                    .split_whitespace()
+                    .skip(3)
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let (before, after) = rest.split_once("reload.rs:").unwrap();
+                let (_, message) = after.split_once("] ").unwrap();
+                format!("{}reload.rs:N] {}", before, message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn switches_apply_from_the_next_event() {
+        let glog = Glog::default();
+        let handle = glog.reload_handle();
+        let output = format_with(glog, GlogFields::default(), false, || {
+            let _span = tracing::info_span!("shave", yak = 1).entered();
+            tracing::info!("a");
+            handle.set_span_context(false);
+            tracing::info!("b");
+            handle.set_target(true);
+            tracing::info!("c");
+            handle.set_thread_names(true);
+            tracing::info!("d");
+            handle.set_target(false);
+            handle.set_thread_names(false);
+            handle.set_span_context(true);
+            tracing::info!("e");
+        });
+        let thread = std::thread::current().name().unwrap().to_string();
+        assert_eq!(
+            after_thread_id(&output),
+            [
+                "src/reload.rs:N] [shave{yak: 1}] a".to_string(),
+                "src/reload.rs:N] b".to_string(),
+                "[tracing_glog::reload::tests] src/reload.rs:N] c".to_string(),
+                format!(
+                    "{} [tracing_glog::reload::tests] src/reload.rs:N] d",
+                    thread
+                ),
+                "src/reload.rs:N] [shave{yak: 1}] e".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn filter_changes_reach_registered_callsites() {
+        let filter = GlogOptions::default().filter();
+        let handle = filter.reload_handle();
+        let capture = crate::testing::Capture::default();
+        let subscriber = tracing_subscriber::registry().with(filter).with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(capture.clone())
+                .with_ansi(false)
+                .event_format(Glog::default())
+                .fmt_fields(GlogFields::default()),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            // The same two callsites are hit every time, so they are registered before the
+            // settings change and are only re-evaluated through the interest cache.
+            let log = |i: i32| {
+                tracing::debug!("debug {}", i);
+                tracing::trace!("trace {}", i);
+            };
+            log(0);
+            handle.set_verbosity(1);
+            log(1);
+            handle.set_verbosity(0);
+            log(2);
+            handle.set_vmodule("reload=2").unwrap();
+            log(3);
+            handle.set_vmodule("other=2").unwrap();
+            log(4);
+            handle.set_vmodule("").unwrap();
+            handle.set_verbosity(2);
+            log(5);
+        });
+        let messages: Vec<String> = after_thread_id(&capture.contents())
+            .into_iter()
+            .map(|line| line.split_once("] ").unwrap().1.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            ["debug 1", "debug 3", "trace 3", "debug 5", "trace 5"]
+        );
+    }
+}
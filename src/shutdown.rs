@@ -0,0 +1,124 @@
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use tracing::Level;
+
+/// A writer created by this crate that holds log lines which have not reached their
+/// destination yet.
+pub(crate) trait Flush: Send + Sync {
+    /// Writes out buffered lines of at least `min_level` severity.
+    fn flush(&self, min_level: Level);
+
+    /// Writes out every buffered line, syncs it to disk where possible, and stops accepting
+    /// new lines if the writer runs in the background.
+    fn shutdown(&self);
+}
+
+/// When a sink is flushed relative to the others. Batches are written first, then background
+/// queues are drained, since each may be feeding the next, and files are flushed last.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Stage {
+    Batch,
+    Queue,
+    File,
+}
+
+static SINKS: Mutex<Vec<(Stage, Weak<dyn Flush>)>> = Mutex::new(Vec::new());
+
+/// Registers a sink so that [`flush`] and [`shutdown`] reach it for as long as it is alive.
+pub(crate) fn register(stage: Stage, sink: Weak<dyn Flush>) {
+    let mut sinks = SINKS.lock().unwrap_or_else(PoisonError::into_inner);
+    sinks.retain(|(_, sink)| sink.strong_count() > 0);
+    sinks.push((stage, sink));
+}
+
+/// Returns the live sinks in flush order, without holding the registry lock while they are
+/// flushed: flushing may log, and logging may register new sinks.
+fn live_sinks() -> Vec<Arc<dyn Flush>> {
+    let mut sinks: Vec<_> = SINKS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .filter_map(|(stage, sink)| Some((*stage, sink.upgrade()?)))
+        .collect();
+    sinks.sort_by_key(|(stage, _)| *stage);
+    sinks.into_iter().map(|(_, sink)| sink).collect()
+}
+
+/// Writes out the log lines of at least `min_level` severity that are buffered by any writer
+/// this crate has created, like glog's `FlushLogFiles`.
+///
+/// Background writers such as [`NonBlocking`] are drained first, then [`LogFiles`] are
+/// flushed. `flush(Level::TRACE)` flushes everything. Flushing does not sync files to disk;
+/// see [`shutdown`] for that.
+///
+/// [`NonBlocking`]: crate::NonBlocking
+/// [`LogFiles`]: crate::LogFiles
+pub fn flush(min_level: Level) {
+    for sink in live_sinks() {
+        sink.flush(min_level);
+    }
+}
+
+/// Drains every background writer this crate has created, then flushes and syncs every log
+/// file to disk, like glog's `ShutdownGoogleLogging`.
+///
+/// Background writers stop once they have been drained, so lines logged to them afterwards
+/// are lost; log files keep accepting lines. Call this as the last thing before the process
+/// exits, or hold a [`ShutdownGuard`] in `main`.
+///
+/// `shutdown` takes locks and performs I/O, so it must not be called from a signal handler
+/// itself. Instead, call it from the thread or task that receives the signal, for instance
+/// with `tokio::signal`:
+///
+/// ```no_run
+/// # #[tokio::main]
+/// # async fn main() {
+/// # #[cfg(unix)]
+/// # {
+/// use tokio::signal::unix::{signal, SignalKind};
+///
+/// let mut sigterm = signal(SignalKind::terminate()).expect("Unable to listen for SIGTERM");
+/// sigterm.recv().await;
+/// tracing_glog::shutdown();
+/// # }
+/// # }
+/// ```
+pub fn shutdown() {
+    for sink in live_sinks() {
+        sink.shutdown();
+    }
+}
+
+/// Calls [`shutdown`] when dropped.
+///
+/// Hold a `ShutdownGuard` for the lifetime of `main` so that no log lines are lost when it
+/// returns, including when it returns early with an error:
+///
+/// ```
+/// use tracing_glog::{Glog, GlogFields, NonBlocking, ShutdownGuard};
+///
+/// let _shutdown = ShutdownGuard::new();
+/// let (writer, _guard) = NonBlocking::new(std::io::stderr());
+/// tracing_subscriber::fmt()
+///     .with_writer(writer)
+///     .event_format(Glog::default())
+///     .fmt_fields(GlogFields::default())
+///     .init();
+/// ```
+#[must_use = "dropping the guard immediately shuts down logging"]
+#[derive(Debug, Default)]
+pub struct ShutdownGuard {
+    _priv: (),
+}
+
+impl ShutdownGuard {
+    /// Returns a guard that calls [`shutdown`] when dropped.
+    pub fn new() -> Self {
+        ShutdownGuard { _priv: () }
+    }
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        shutdown();
+    }
+}
@@ -0,0 +1,93 @@
+//! Fixtures shared by the unit tests.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use tracing_subscriber::{
+    fmt::{format::FormatEvent, FormatFields, MakeWriter},
+    Registry,
+};
+
+/// A writer that keeps everything written to it. Clones share the same buffer, so a clone
+/// can be given to a subscriber and the original read afterwards.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Capture {
+    /// Returns everything written so far.
+    pub(crate) fn bytes(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Returns everything written so far, which must be UTF-8.
+    pub(crate) fn contents(&self) -> String {
+        String::from_utf8(self.bytes()).unwrap()
+    }
+}
+
+impl io::Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Capture {
+    type Writer = Capture;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// Formats the events of every level logged by `log` with `format` and `fields`, writing ANSI
+/// escapes if `ansi` is true, and returns the output.
+pub(crate) fn format_with<E, N>(format: E, fields: N, ansi: bool, log: impl FnOnce()) -> String
+where
+    E: FormatEvent<Registry, N> + Send + Sync + 'static,
+    N: for<'w> FormatFields<'w> + Send + Sync + 'static,
+{
+    let capture = Capture::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(capture.clone())
+        .with_ansi(ansi)
+        .with_max_level(tracing::Level::TRACE)
+        .fmt_fields(fields)
+        .event_format(format)
+        .finish();
+    tracing::subscriber::with_default(subscriber, log);
+    capture.contents()
+}
+
+/// Returns an empty directory for the test called `name`.
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tracing-glog-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Returns the lines written to the log file of the given severity in `dir`, without its
+/// header. There must be only one such file.
+pub(crate) fn lines_in(dir: &Path, severity: &str) -> String {
+    let path = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| {
+            !path.is_symlink()
+                && path
+                    .to_str()
+                    .unwrap()
+                    .contains(&format!(".log.{}.", severity))
+        })
+        .unwrap();
+    let contents = fs::read_to_string(path).unwrap();
+    let (_, lines) = contents.split_once("msg\n").unwrap();
+    lines.to_string()
+}
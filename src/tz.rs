@@ -0,0 +1,888 @@
+use crate::{format::format_datetime, GlogDialect};
+use std::{error, fmt, fs, io, path::PathBuf, sync::Arc};
+use time::{
+    format_description::FormatItem, formatting::Formattable, Date, Month, OffsetDateTime, UtcOffset,
+};
+use tracing_subscriber::fmt::{format::Writer, time::FormatTime};
+
+/// Formats the current time in an explicit [`TimeZone`], regardless of the host's
+/// configured time zone.
+///
+/// Unlike [`LocalTime`], this never needs to query the operating system for the local
+/// offset, so it is sound in multi-threaded processes and follows daylight saving time
+/// transitions as described by the time zone's rules.
+///
+/// # Examples
+///
+/// ```no_run
+/// use tracing_glog::{Glog, GlogFields, ZonedTime};
+///
+/// let timer = ZonedTime::from_name("America/New_York").expect("unknown time zone");
+/// tracing_subscriber::fmt()
+///     .event_format(Glog::default().with_timer(timer))
+///     .fmt_fields(GlogFields::default())
+///     .init();
+/// ```
+///
+/// [`LocalTime`]: crate::LocalTime
+#[derive(Clone, Debug)]
+pub struct ZonedTime<F = Vec<FormatItem<'static>>> {
+    zone: Arc<TimeZone>,
+    format: F,
+}
+
+impl<F> ZonedTime<F>
+where
+    F: Formattable,
+{
+    /// Returns a timer that formats the current time in `zone` using the provided [formatter].
+    ///
+    /// [formatter]: time::formatting::Formattable
+    pub fn new(zone: TimeZone, format: F) -> Self {
+        Self {
+            zone: Arc::new(zone),
+            format,
+        }
+    }
+
+    /// Returns the time zone used by this timer.
+    pub fn zone(&self) -> &TimeZone {
+        &self.zone
+    }
+}
+
+impl ZonedTime {
+    /// Returns a timer in the default glog format for the named [IANA time zone], such as
+    /// `America/New_York`.
+    ///
+    /// See [`TimeZone::from_name`] for how the zone is looked up.
+    ///
+    /// [IANA time zone]: https://www.iana.org/time-zones
+    pub fn from_name(name: &str) -> Result<Self, TzError> {
+        Ok(Self::new(
+            TimeZone::from_name(name)?,
+            GlogDialect::Glog.time_format(),
+        ))
+    }
+}
+
+impl<F> FormatTime for ZonedTime<F>
+where
+    F: Formattable,
+{
+    fn format_time(&self, writer: &mut Writer<'_>) -> fmt::Result {
+        let now = self.zone.to_zone(OffsetDateTime::now_utc());
+        format_datetime(writer, now, &self.format)
+    }
+}
+
+/// A time zone loaded from the [IANA time zone database].
+///
+/// Time zones can be loaded from the system's zoneinfo directory with
+/// [`TimeZone::from_name`], from the contents of a [TZif] file with [`TimeZone::from_tzif`]
+/// (for instance, one embedded with `include_bytes!`), or from a POSIX `TZ` rule with
+/// [`TimeZone::from_posix`].
+///
+/// Besides driving [`ZonedTime`], a `TimeZone` can be used to normalize timestamps that were
+/// recorded in different zones:
+///
+/// ```
+/// use time::macros::{datetime, offset};
+/// use tracing_glog::TimeZone;
+///
+/// let new_york = TimeZone::from_posix("EST5EDT,M3.2.0,M11.1.0").unwrap();
+///
+/// assert_eq!(new_york.offset_at(datetime!(2023-01-15 12:00 UTC)), offset!(-5));
+/// assert_eq!(new_york.offset_at(datetime!(2023-07-15 12:00 UTC)), offset!(-4));
+/// // Daylight saving time starts at 02:00 local standard time on March 12th, 2023.
+/// assert_eq!(new_york.offset_at(datetime!(2023-03-12 06:59:59 UTC)), offset!(-5));
+/// assert_eq!(new_york.offset_at(datetime!(2023-03-12 07:00 UTC)), offset!(-4));
+/// ```
+///
+/// [IANA time zone database]: https://www.iana.org/time-zones
+/// [TZif]: https://www.rfc-editor.org/rfc/rfc8536
+#[derive(Clone, Debug)]
+pub struct TimeZone {
+    name: String,
+    /// Transition times, in seconds since the Unix epoch, in ascending order.
+    transitions: Vec<i64>,
+    /// For each transition, the index into `offsets` that applies from then on.
+    transition_offsets: Vec<usize>,
+    /// Offsets from UTC, in seconds.
+    offsets: Vec<i32>,
+    /// The rule to use after the last transition.
+    rule: Option<PosixRule>,
+}
+
+impl TimeZone {
+    /// Loads the named time zone, such as `America/New_York`, from the system's zoneinfo
+    /// directory.
+    ///
+    /// The directory named by the `TZDIR` environment variable is searched first, followed by
+    /// `/usr/share/zoneinfo`.
+    pub fn from_name(name: &str) -> Result<Self, TzError> {
+        let valid = !name.is_empty()
+            && !name.starts_with('/')
+            && name
+                .split('/')
+                .all(|part| !part.is_empty() && part != "." && part != "..");
+        if !valid {
+            return Err(TzError::InvalidName(name.to_string()));
+        }
+
+        let dirs = std::env::var_os("TZDIR")
+            .map(PathBuf::from)
+            .into_iter()
+            .chain(Some(PathBuf::from("/usr/share/zoneinfo")));
+        let mut last_err = None;
+        for dir in dirs {
+            match fs::read(dir.join(name)) {
+                Ok(bytes) => return Self::from_tzif(name, &bytes),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(TzError::Io(
+            name.to_string(),
+            last_err.expect("at least one directory is searched"),
+        ))
+    }
+
+    /// Loads the local time zone from the `TZ` environment variable or, if it is not set,
+    /// from `/etc/localtime`.
+    pub(crate) fn local() -> Result<Self, TzError> {
+        Self::from_tz_var(std::env::var("TZ").ok().as_deref())
+    }
+
+    /// Loads the time zone described by a value of the `TZ` environment variable, as the C
+    /// library does: a POSIX rule, a name in the zoneinfo directory, or an absolute path,
+    /// optionally preceded by `:`. An empty value is UTC.
+    fn from_tz_var(tz: Option<&str>) -> Result<Self, TzError> {
+        let tz = match tz {
+            None => return Self::from_file("/etc/localtime"),
+            Some("") => return Self::from_posix("UTC0"),
+            Some(tz) => tz,
+        };
+        match tz.strip_prefix(':') {
+            Some(path) if path.starts_with('/') => Self::from_file(path),
+            Some(name) => Self::from_name(name),
+            None if tz.starts_with('/') => Self::from_file(tz),
+            None => Self::from_posix(tz).or_else(|_| Self::from_name(tz)),
+        }
+    }
+
+    fn from_file(path: &str) -> Result<Self, TzError> {
+        let bytes = fs::read(path).map_err(|e| TzError::Io(path.to_string(), e))?;
+        Self::from_tzif(path, &bytes)
+    }
+
+    /// Parses the contents of a [TZif] file, as found in the zoneinfo directory.
+    ///
+    /// [TZif]: https://www.rfc-editor.org/rfc/rfc8536
+    pub fn from_tzif(name: &str, bytes: &[u8]) -> Result<Self, TzError> {
+        let invalid = |reason| TzError::InvalidTzif(name.to_string(), reason);
+
+        let mut data = Reader(bytes);
+        let header = Header::parse(&mut data).ok_or_else(|| invalid("invalid header"))?;
+        let (header, time_size) = if header.version >= b'2' {
+            // Skip the version 1 data block in favour of the 64-bit one that follows it.
+            header
+                .v1_len()
+                .and_then(|len| data.take(len))
+                .ok_or_else(|| invalid("truncated data"))?;
+            let header = Header::parse(&mut data).ok_or_else(|| invalid("invalid header"))?;
+            (header, 8)
+        } else {
+            (header, 4)
+        };
+
+        // Counts are only trusted as far as there is data left to back them.
+        let capacity =
+            |count: usize, size: usize, data: &Reader<'_>| count.min(data.0.len() / size);
+        let mut transitions = Vec::with_capacity(capacity(header.timecnt, time_size, &data));
+        for _ in 0..header.timecnt {
+            let time = data
+                .int(time_size)
+                .ok_or_else(|| invalid("truncated data"))?;
+            transitions.push(time);
+        }
+        let mut transition_offsets = Vec::with_capacity(capacity(header.timecnt, 1, &data));
+        for _ in 0..header.timecnt {
+            let index = data.int(1).ok_or_else(|| invalid("truncated data"))? as usize;
+            if index >= header.typecnt {
+                return Err(invalid("invalid local time type"));
+            }
+            transition_offsets.push(index);
+        }
+        let mut offsets = Vec::with_capacity(capacity(header.typecnt, 6, &data));
+        for _ in 0..header.typecnt {
+            let offset = data.int(4).ok_or_else(|| invalid("truncated data"))?;
+            // Skip `isdst` and `desigidx`.
+            data.take(2).ok_or_else(|| invalid("truncated data"))?;
+            offsets.push(offset as i32);
+        }
+        if offsets.is_empty() {
+            return Err(invalid("no local time types"));
+        }
+        // Skip the abbreviations, leap seconds and standard/UT indicators.
+        let leap_len = header.leapcnt.checked_mul(time_size + 4);
+        [
+            Some(header.charcnt),
+            leap_len,
+            Some(header.isstdcnt),
+            Some(header.isutcnt),
+        ]
+        .into_iter()
+        .try_for_each(|len| data.take(len?).map(drop))
+        .ok_or_else(|| invalid("truncated data"))?;
+
+        let rule = if time_size == 8 {
+            let footer = std::str::from_utf8(data.0)
+                .ok()
+                .and_then(|footer| footer.strip_prefix('\n'))
+                .and_then(|footer| footer.split('\n').next())
+                .ok_or_else(|| invalid("invalid footer"))?;
+            if footer.is_empty() {
+                None
+            } else {
+                Some(
+                    PosixRule::parse(footer)
+                        .ok_or_else(|| TzError::InvalidRule(footer.to_string()))?,
+                )
+            }
+        } else {
+            None
+        };
+
+        Ok(TimeZone {
+            name: name.to_string(),
+            transitions,
+            transition_offsets,
+            offsets,
+            rule,
+        })
+    }
+
+    /// Creates a time zone from a POSIX `TZ` rule, such as `EST5EDT,M3.2.0,M11.1.0` or
+    /// `CET-1CEST,M3.5.0,M10.5.0/3`.
+    pub fn from_posix(rule: &str) -> Result<Self, TzError> {
+        let parsed =
+            PosixRule::parse(rule).ok_or_else(|| TzError::InvalidRule(rule.to_string()))?;
+        Ok(TimeZone {
+            name: rule.to_string(),
+            transitions: Vec::new(),
+            transition_offsets: Vec::new(),
+            offsets: vec![parsed.std_offset],
+            rule: Some(parsed),
+        })
+    }
+
+    /// Returns the name this time zone was loaded with.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the offset from UTC in effect in this time zone at the given instant.
+    pub fn offset_at(&self, instant: OffsetDateTime) -> UtcOffset {
+        let timestamp = instant.unix_timestamp();
+        let seconds = match self.transitions.last() {
+            Some(&last) if timestamp >= last && self.rule.is_some() => {
+                self.rule.as_ref().unwrap().offset_at(timestamp)
+            }
+            Some(_) => match self.transitions.binary_search(&timestamp) {
+                Ok(i) => self.offsets[self.transition_offsets[i]],
+                // Before the first transition, the first local time type applies.
+                Err(0) => self.offsets[0],
+                Err(i) => self.offsets[self.transition_offsets[i - 1]],
+            },
+            None => match &self.rule {
+                Some(rule) => rule.offset_at(timestamp),
+                None => self.offsets[0],
+            },
+        };
+        UtcOffset::from_whole_seconds(seconds).unwrap_or(UtcOffset::UTC)
+    }
+
+    /// Converts the given instant to the local time in this time zone.
+    pub fn to_zone(&self, instant: OffsetDateTime) -> OffsetDateTime {
+        instant.to_offset(self.offset_at(instant))
+    }
+}
+
+/// An error returned when a [`TimeZone`] cannot be loaded.
+#[derive(Debug)]
+pub enum TzError {
+    /// The time zone name is not a relative path within the zoneinfo directory.
+    InvalidName(String),
+    /// The time zone file could not be read.
+    Io(String, io::Error),
+    /// The time zone file is not a valid TZif file.
+    InvalidTzif(String, &'static str),
+    /// The POSIX `TZ` rule could not be parsed.
+    InvalidRule(String),
+}
+
+impl fmt::Display for TzError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TzError::InvalidName(name) => write!(f, "invalid time zone name `{}`", name),
+            TzError::Io(name, _) => write!(f, "unable to read time zone `{}`", name),
+            TzError::InvalidTzif(name, reason) => {
+                write!(f, "invalid time zone file for `{}`: {}", name, reason)
+            }
+            TzError::InvalidRule(rule) => write!(f, "invalid POSIX TZ rule `{}`", rule),
+        }
+    }
+}
+
+impl error::Error for TzError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            TzError::Io(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A cursor over the bytes of a TZif file.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Some(head)
+    }
+
+    /// Reads a big-endian signed integer of `size` bytes.
+    fn int(&mut self, size: usize) -> Option<i64> {
+        let bytes = self.take(size)?;
+        let value = match size {
+            1 => bytes[0] as i64,
+            4 => i32::from_be_bytes(bytes.try_into().ok()?) as i64,
+            8 => i64::from_be_bytes(bytes.try_into().ok()?),
+            _ => unreachable!("unsupported integer size"),
+        };
+        Some(value)
+    }
+}
+
+struct Header {
+    version: u8,
+    isutcnt: usize,
+    isstdcnt: usize,
+    leapcnt: usize,
+    timecnt: usize,
+    typecnt: usize,
+    charcnt: usize,
+}
+
+impl Header {
+    fn parse(data: &mut Reader<'_>) -> Option<Self> {
+        if data.take(4)? != b"TZif" {
+            return None;
+        }
+        let version = data.take(1)?[0];
+        data.take(15)?;
+        // Counts are stored as signed integers, and negative ones are invalid.
+        let mut count = || data.int(4).and_then(|n| usize::try_from(n).ok());
+        Some(Header {
+            version,
+            isutcnt: count()?,
+            isstdcnt: count()?,
+            leapcnt: count()?,
+            timecnt: count()?,
+            typecnt: count()?,
+            charcnt: count()?,
+        })
+    }
+
+    /// The length of the version 1 data block that follows this header, or `None` if it
+    /// does not fit in a `usize`.
+    fn v1_len(&self) -> Option<usize> {
+        [
+            self.timecnt.checked_mul(5)?,
+            self.typecnt.checked_mul(6)?,
+            self.charcnt,
+            self.leapcnt.checked_mul(8)?,
+            self.isstdcnt,
+            self.isutcnt,
+        ]
+        .into_iter()
+        .try_fold(0usize, usize::checked_add)
+    }
+}
+
+/// A POSIX `TZ` rule, as found in the footer of TZif files.
+#[derive(Clone, Debug)]
+struct PosixRule {
+    /// The standard time offset from UTC, in seconds.
+    std_offset: i32,
+    dst: Option<DstRule>,
+}
+
+#[derive(Clone, Debug)]
+struct DstRule {
+    offset: i32,
+    start: RuleDate,
+    /// The local (standard) time of day at which daylight saving time starts, in seconds.
+    start_time: i32,
+    end: RuleDate,
+    /// The local (daylight saving) time of day at which daylight saving time ends, in seconds.
+    end_time: i32,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum RuleDate {
+    /// `Jn`: the one-based day of the year, never counting February 29th.
+    Julian(u16),
+    /// `n`: the zero-based day of the year, counting February 29th.
+    Ordinal(u16),
+    /// `Mm.w.d`: day `d` (0 is Sunday) of week `w` (5 is the last) of month `m`.
+    MonthWeekDay(u8, u8, u8),
+}
+
+impl PosixRule {
+    fn parse(rule: &str) -> Option<Self> {
+        let mut s = RuleParser(rule);
+        s.name()?;
+        // POSIX offsets are the time to add to local time to get UTC.
+        let std_offset = -s.offset()?;
+        if s.0.is_empty() {
+            return Some(PosixRule {
+                std_offset,
+                dst: None,
+            });
+        }
+
+        s.name()?;
+        let offset = match s.0.chars().next() {
+            Some(',') | None => std_offset + 3600,
+            Some(_) => -s.offset()?,
+        };
+        // Without explicit dates, US rules are the POSIX default.
+        let (start, start_time, end, end_time) = if s.0.is_empty() {
+            (
+                RuleDate::MonthWeekDay(3, 2, 0),
+                7200,
+                RuleDate::MonthWeekDay(11, 1, 0),
+                7200,
+            )
+        } else {
+            s.expect(',')?;
+            let start = s.date()?;
+            let start_time = if s.eat('/') { s.time()? } else { 7200 };
+            s.expect(',')?;
+            let end = s.date()?;
+            let end_time = if s.eat('/') { s.time()? } else { 7200 };
+            (start, start_time, end, end_time)
+        };
+        if !s.0.is_empty() {
+            return None;
+        }
+
+        Some(PosixRule {
+            std_offset,
+            dst: Some(DstRule {
+                offset,
+                start,
+                start_time,
+                end,
+                end_time,
+            }),
+        })
+    }
+
+    fn offset_at(&self, timestamp: i64) -> i32 {
+        let dst = match &self.dst {
+            Some(dst) => dst,
+            None => return self.std_offset,
+        };
+        let year = match OffsetDateTime::from_unix_timestamp(timestamp + self.std_offset as i64) {
+            Ok(local) => local.year(),
+            Err(_) => return self.std_offset,
+        };
+        let (start, end) = match (
+            dst.start
+                .unix_timestamp(year, dst.start_time, self.std_offset),
+            dst.end.unix_timestamp(year, dst.end_time, dst.offset),
+        ) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return self.std_offset,
+        };
+
+        let in_dst = if start < end {
+            start <= timestamp && timestamp < end
+        } else {
+            // Southern hemisphere: daylight saving time spans the new year.
+            !(end <= timestamp && timestamp < start)
+        };
+        if in_dst {
+            dst.offset
+        } else {
+            self.std_offset
+        }
+    }
+}
+
+impl RuleDate {
+    /// Returns the Unix timestamp at which this rule applies in `year`, given a local time of
+    /// day and the offset from UTC that is in effect at that local time.
+    fn unix_timestamp(self, year: i32, time_of_day: i32, offset: i32) -> Option<i64> {
+        let is_leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let date = match self {
+            RuleDate::Julian(day) => {
+                let ordinal = if is_leap && day >= 60 { day + 1 } else { day };
+                Date::from_ordinal_date(year, ordinal).ok()?
+            }
+            RuleDate::Ordinal(day) => Date::from_ordinal_date(year, day + 1).ok()?,
+            RuleDate::MonthWeekDay(month, week, weekday) => {
+                let month = Month::try_from(month).ok()?;
+                let first = Date::from_calendar_date(year, month, 1).ok()?;
+                let first_weekday = first.weekday().number_days_from_sunday();
+                let mut day = 1 + (7 + weekday - first_weekday) % 7 + (week - 1) * 7;
+                let days_in_month = match month.next() {
+                    Month::January => 31,
+                    next => {
+                        (Date::from_calendar_date(year, next, 1).ok()? - first).whole_days() as u8
+                    }
+                };
+                while day > days_in_month {
+                    day -= 7;
+                }
+                Date::from_calendar_date(year, month, day).ok()?
+            }
+        };
+        let midnight = date.midnight().assume_utc().unix_timestamp();
+        Some(midnight + time_of_day as i64 - offset as i64)
+    }
+}
+
+struct RuleParser<'a>(&'a str);
+
+impl<'a> RuleParser<'a> {
+    fn eat(&mut self, c: char) -> bool {
+        match self.0.strip_prefix(c) {
+            Some(rest) => {
+                self.0 = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Option<()> {
+        self.eat(c).then_some(())
+    }
+
+    /// Parses a zone abbreviation, either alphabetic or quoted in `<>`.
+    fn name(&mut self) -> Option<&'a str> {
+        let (name, rest) = if let Some(quoted) = self.0.strip_prefix('<') {
+            let end = quoted.find('>')?;
+            (&quoted[..end], &quoted[end + 1..])
+        } else {
+            let end = self
+                .0
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(self.0.len());
+            self.0.split_at(end)
+        };
+        if name.len() < 3 {
+            return None;
+        }
+        self.0 = rest;
+        Some(name)
+    }
+
+    fn number(&mut self) -> Option<i32> {
+        let end = self
+            .0
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.0.len());
+        let (digits, rest) = self.0.split_at(end);
+        let number = digits.parse().ok()?;
+        self.0 = rest;
+        Some(number)
+    }
+
+    /// Parses an offset from UTC, `[+-]hh[:mm[:ss]]` with hours in `0..=24`, into seconds.
+    fn offset(&mut self) -> Option<i32> {
+        self.hms(24)
+    }
+
+    /// Parses the time of day of a transition, `[+-]hh[:mm[:ss]]` with hours in
+    /// `-167..=167`, into seconds.
+    fn time(&mut self) -> Option<i32> {
+        self.hms(167)
+    }
+
+    fn hms(&mut self, max_hours: i32) -> Option<i32> {
+        let sign = if self.eat('-') {
+            -1
+        } else {
+            self.eat('+');
+            1
+        };
+        let hours = self.number().filter(|hours| *hours <= max_hours)?;
+        let mut seconds = hours * 3600;
+        if self.eat(':') {
+            seconds += self.number().filter(|minutes| *minutes < 60)? * 60;
+            if self.eat(':') {
+                seconds += self.number().filter(|seconds| *seconds < 60)?;
+            }
+        }
+        Some(sign * seconds)
+    }
+
+    fn date(&mut self) -> Option<RuleDate> {
+        if self.eat('J') {
+            let day = self.number()?;
+            (1..=365)
+                .contains(&day)
+                .then_some(RuleDate::Julian(day as u16))
+        } else if self.eat('M') {
+            let month = self.number()?;
+            self.expect('.')?;
+            let week = self.number()?;
+            self.expect('.')?;
+            let weekday = self.number()?;
+            ((1..=12).contains(&month) && (1..=5).contains(&week) && (0..=6).contains(&weekday))
+                .then_some(RuleDate::MonthWeekDay(
+                    month as u8,
+                    week as u8,
+                    weekday as u8,
+                ))
+        } else {
+            let day = self.number()?;
+            (0..=365)
+                .contains(&day)
+                .then_some(RuleDate::Ordinal(day as u16))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::{datetime, offset};
+
+    /// Builds a TZif header and data block with `time_size`-byte transition times, and one
+    /// local time type per offset.
+    fn block(version: u8, time_size: usize, transitions: &[(i64, u8)], offsets: &[i32]) -> Vec<u8> {
+        let mut bytes = b"TZif".to_vec();
+        bytes.push(version);
+        bytes.extend([0; 15]);
+        let counts = [0, 0, 0, transitions.len(), offsets.len(), 4];
+        for count in counts {
+            bytes.extend((count as u32).to_be_bytes());
+        }
+        for (time, _) in transitions {
+            bytes.extend(&time.to_be_bytes()[8 - time_size..]);
+        }
+        bytes.extend(transitions.iter().map(|(_, index)| index));
+        for offset in offsets {
+            bytes.extend(offset.to_be_bytes());
+            bytes.extend([0, 0]);
+        }
+        bytes.extend(b"UTC\0");
+        bytes
+    }
+
+    /// Builds a version 2 TZif file, whose version 1 block has the same transitions.
+    fn v2(transitions: &[(i64, u8)], offsets: &[i32], footer: &str) -> Vec<u8> {
+        let mut bytes = block(b'2', 4, transitions, offsets);
+        bytes.extend(block(b'2', 8, transitions, offsets));
+        bytes.extend(format!("\n{}\n", footer).bytes());
+        bytes
+    }
+
+    fn header(version: u8, counts: [u32; 6]) -> Vec<u8> {
+        let mut bytes = b"TZif".to_vec();
+        bytes.push(version);
+        bytes.extend([0; 15]);
+        for count in counts {
+            bytes.extend(count.to_be_bytes());
+        }
+        bytes
+    }
+
+    // 2023-03-26 01:00 UTC and 2023-10-29 01:00 UTC, Europe's transitions in 2023.
+    const SUMMER: i64 = 1_679_792_400;
+    const WINTER: i64 = 1_698_541_200;
+
+    #[test]
+    fn tzif_v1() {
+        let bytes = block(0, 4, &[(SUMMER, 1), (WINTER, 0)], &[3600, 7200]);
+        let zone = TimeZone::from_tzif("Europe/Paris", &bytes).unwrap();
+        assert_eq!(zone.name(), "Europe/Paris");
+        assert_eq!(zone.offset_at(datetime!(2023-01-01 0:00 UTC)), offset!(+1));
+        assert_eq!(
+            zone.offset_at(datetime!(2023-03-26 0:59:59 UTC)),
+            offset!(+1)
+        );
+        assert_eq!(zone.offset_at(datetime!(2023-03-26 1:00 UTC)), offset!(+2));
+        assert_eq!(zone.offset_at(datetime!(2023-10-29 1:00 UTC)), offset!(+1));
+        // Without a footer, the last transition applies forever.
+        assert_eq!(zone.offset_at(datetime!(2030-07-01 0:00 UTC)), offset!(+1));
+    }
+
+    #[test]
+    fn tzif_v2_footer_after_last_transition() {
+        let bytes = v2(
+            &[(SUMMER, 1), (WINTER, 0)],
+            &[3600, 7200],
+            "CET-1CEST,M3.5.0,M10.5.0/3",
+        );
+        let zone = TimeZone::from_tzif("Europe/Paris", &bytes).unwrap();
+        assert_eq!(zone.offset_at(datetime!(2023-07-01 0:00 UTC)), offset!(+2));
+        assert_eq!(zone.offset_at(datetime!(2030-01-01 0:00 UTC)), offset!(+1));
+        assert_eq!(zone.offset_at(datetime!(2030-07-01 0:00 UTC)), offset!(+2));
+        // Summer time starts on the last Sunday of March, at 01:00 UTC.
+        assert_eq!(
+            zone.offset_at(datetime!(2030-03-31 0:59:59 UTC)),
+            offset!(+1)
+        );
+        assert_eq!(zone.offset_at(datetime!(2030-03-31 1:00 UTC)), offset!(+2));
+    }
+
+    #[test]
+    fn tzif_v2_without_footer_rule() {
+        let bytes = v2(&[(SUMMER, 1)], &[0, 3600], "");
+        let zone = TimeZone::from_tzif("Test", &bytes).unwrap();
+        assert_eq!(zone.offset_at(datetime!(2030-01-01 0:00 UTC)), offset!(+1));
+    }
+
+    #[test]
+    fn southern_hemisphere_rule() {
+        let sydney = TimeZone::from_posix("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(
+            sydney.offset_at(datetime!(2023-01-15 0:00 UTC)),
+            offset!(+11)
+        );
+        assert_eq!(
+            sydney.offset_at(datetime!(2023-07-15 0:00 UTC)),
+            offset!(+10)
+        );
+        assert_eq!(
+            sydney.offset_at(datetime!(2023-12-31 23:00 UTC)),
+            offset!(+11)
+        );
+        // Daylight saving time ends at 03:00 local daylight time on April 2nd, 2023, and
+        // starts at 02:00 local standard time on October 1st, 2023.
+        assert_eq!(
+            sydney.offset_at(datetime!(2023-04-01 15:59:59 UTC)),
+            offset!(+11)
+        );
+        assert_eq!(
+            sydney.offset_at(datetime!(2023-04-01 16:00 UTC)),
+            offset!(+10)
+        );
+        assert_eq!(
+            sydney.offset_at(datetime!(2023-09-30 15:59:59 UTC)),
+            offset!(+10)
+        );
+        assert_eq!(
+            sydney.offset_at(datetime!(2023-09-30 16:00 UTC)),
+            offset!(+11)
+        );
+    }
+
+    fn tzif_error(bytes: &[u8]) -> &'static str {
+        match TimeZone::from_tzif("Test", bytes) {
+            Err(TzError::InvalidTzif(_, reason)) => reason,
+            other => panic!("expected an invalid TZif file, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn truncated_tzif() {
+        let bytes = block(0, 4, &[(SUMMER, 1), (WINTER, 0)], &[3600, 7200]);
+        assert_eq!(tzif_error(&bytes[..3]), "invalid header");
+        assert_eq!(tzif_error(&bytes[..40]), "invalid header");
+        for len in 44..bytes.len() {
+            assert_eq!(tzif_error(&bytes[..len]), "truncated data");
+        }
+
+        let bytes = v2(&[(SUMMER, 1)], &[0, 3600], "");
+        let v1_len = block(b'2', 4, &[(SUMMER, 1)], &[0, 3600]).len();
+        assert_eq!(tzif_error(&bytes[..v1_len - 1]), "truncated data");
+        assert_eq!(tzif_error(&bytes[..v1_len + 44]), "truncated data");
+        assert_eq!(tzif_error(&bytes[..bytes.len() - 2]), "invalid footer");
+    }
+
+    #[test]
+    fn malformed_tzif() {
+        assert_eq!(tzif_error(b"TZjf\0"), "invalid header");
+        // A negative count.
+        assert_eq!(
+            tzif_error(&header(0, [0, 0, 0, u32::MAX, 1, 0])),
+            "invalid header"
+        );
+        // Counts that promise far more data than there is.
+        assert_eq!(
+            tzif_error(&header(0, [0, 0, 0, 0x7fff_ffff, 1, 0])),
+            "truncated data"
+        );
+        assert_eq!(
+            tzif_error(&header(b'2', [0, 0, 0, 0x7fff_ffff, 1, 0])),
+            "truncated data"
+        );
+        let counts = [0x7fff_ffff; 6];
+        assert_eq!(tzif_error(&header(b'2', counts)), "truncated data");
+        let counts = [0, 0, 0x7fff_ffff, 0, 1, 0];
+        assert_eq!(tzif_error(&header(0, counts)), "truncated data");
+
+        assert_eq!(tzif_error(&block(0, 4, &[], &[])), "no local time types");
+        let bytes = block(0, 4, &[(SUMMER, 2)], &[0, 3600]);
+        assert_eq!(tzif_error(&bytes), "invalid local time type");
+
+        let bytes = v2(&[], &[0], "EST99999999");
+        assert!(matches!(
+            TimeZone::from_tzif("Test", &bytes),
+            Err(TzError::InvalidRule(_))
+        ));
+    }
+
+    #[test]
+    fn posix_rule_bounds() {
+        assert!(TimeZone::from_posix("EST99999999").is_err());
+        assert!(TimeZone::from_posix("EST99999999999999").is_err());
+        assert!(TimeZone::from_posix("EST25").is_err());
+        assert!(TimeZone::from_posix("EST5:60").is_err());
+        assert!(TimeZone::from_posix("EST-24").is_ok());
+        assert!(TimeZone::from_posix("EST5EDT25").is_err());
+
+        assert!(TimeZone::from_posix("EST5EDT,M3.2.0/167,M11.1.0/-167").is_ok());
+        assert!(TimeZone::from_posix("EST5EDT,M3.2.0/168,M11.1.0").is_err());
+        assert!(TimeZone::from_posix("EST5EDT,M3.2.0,M11.1.0/-168").is_err());
+        assert!(TimeZone::from_posix("EST5EDT,M13.2.0,M11.1.0").is_err());
+        assert!(TimeZone::from_posix("EST5EDT,J0,J365").is_err());
+        assert!(TimeZone::from_posix("ES5").is_err());
+    }
+
+    #[test]
+    fn tz_variable() {
+        let offset_at = |tz| {
+            TimeZone::from_tz_var(Some(tz))
+                .unwrap()
+                .offset_at(time::macros::datetime!(2023-01-15 12:00 UTC))
+                .whole_hours()
+        };
+        assert_eq!(offset_at(""), 0);
+        assert_eq!(offset_at("EST5EDT,M3.2.0,M11.1.0"), -5);
+        assert_eq!(offset_at("<+0330>-3:30"), 3);
+
+        for missing in ["/nonexistent/zone", ":/nonexistent/zone"] {
+            match TimeZone::from_tz_var(Some(missing)) {
+                Err(TzError::Io(path, _)) => assert_eq!(path, "/nonexistent/zone"),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        assert!(matches!(
+            TimeZone::from_tz_var(Some(":../zone")),
+            Err(TzError::InvalidName(_))
+        ));
+    }
+}
@@ -0,0 +1,310 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+use tracing_subscriber::fmt::{format::Writer, time::FormatTime};
+
+/// Formats the time elapsed since the process or subscriber started, such as `+12.345678s`.
+///
+/// `Uptime` can replace the wall-clock timer entirely with [`Glog::with_timer`], but since
+/// glog parsers expect a date and time after the severity, it is usually better to keep the
+/// wall-clock timer and record the uptime in a bracketed extension with
+/// [`Glog::with_bracketed_timer`]:
+///
+/// ```
+/// use tracing_glog::{Glog, GlogFields, Uptime};
+///
+/// tracing_subscriber::fmt()
+///     .event_format(Glog::default().with_bracketed_timer(Uptime::since_process_start()))
+///     .fmt_fields(GlogFields::default())
+///     .init();
+///
+/// // I1201 01:13:04.724801 1025672 lib.rs:34] [+0.001234s] preparing to shave yaks
+/// ```
+///
+/// [`Glog::with_timer`]: crate::Glog::with_timer
+/// [`Glog::with_bracketed_timer`]: crate::Glog::with_bracketed_timer
+#[derive(Clone, Copy, Debug)]
+pub struct Uptime {
+    start: Instant,
+}
+
+impl Uptime {
+    /// Returns a timer that measures the time elapsed since `start`.
+    pub fn since(start: Instant) -> Self {
+        Uptime { start }
+    }
+
+    /// Returns a timer that measures the time elapsed since the process started.
+    ///
+    /// On Linux, the process start time is read from `/proc`, which has a resolution of a
+    /// few milliseconds. On other platforms, or if `/proc` is unavailable, this measures the
+    /// time elapsed since this function was called instead.
+    pub fn since_process_start() -> Self {
+        let now = Instant::now();
+        let start = process_age()
+            .and_then(|age| now.checked_sub(age))
+            .unwrap_or(now);
+        Uptime { start }
+    }
+}
+
+impl Default for Uptime {
+    /// Returns a timer that measures the time elapsed since it was created.
+    fn default() -> Self {
+        Uptime::since(Instant::now())
+    }
+}
+
+impl FormatTime for Uptime {
+    fn format_time(&self, writer: &mut Writer<'_>) -> fmt::Result {
+        write!(writer, "+{}s", Seconds(self.start.elapsed()))
+    }
+}
+
+/// Formats a raw reading of the system's monotonic clock in seconds, such as
+/// `1234567.891011`.
+///
+/// On Unix, this is `CLOCK_MONOTONIC`, so readings can be correlated with other processes on
+/// the same host (for instance, with `perf` or kernel logs). On other platforms, the reading
+/// is relative to the first time a `Monotonic` timer was created.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Monotonic {
+    _priv: (),
+}
+
+impl Monotonic {
+    /// Returns a timer that reads the system's monotonic clock.
+    pub fn new() -> Self {
+        #[cfg(not(unix))]
+        epoch();
+        Monotonic { _priv: () }
+    }
+}
+
+impl FormatTime for Monotonic {
+    fn format_time(&self, writer: &mut Writer<'_>) -> fmt::Result {
+        write!(writer, "{}", Seconds(monotonic_now()))
+    }
+}
+
+/// Renders a duration as seconds with microsecond precision.
+struct Seconds(Duration);
+
+impl fmt::Display for Seconds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:06}", self.0.as_secs(), self.0.subsec_micros())
+    }
+}
+
+#[cfg(unix)]
+fn monotonic_now() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is a valid, writable `timespec`, and `CLOCK_MONOTONIC` is always supported.
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+#[cfg(not(unix))]
+fn epoch() -> Instant {
+    static EPOCH: std::sync::OnceLock<Instant> = std::sync::OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+#[cfg(not(unix))]
+fn monotonic_now() -> Duration {
+    epoch().elapsed()
+}
+
+/// Returns how long ago the current process started, if it can be determined.
+#[cfg(target_os = "linux")]
+fn process_age() -> Option<Duration> {
+    process_age_in(std::path::Path::new("/proc"))
+}
+
+/// Reads the age of the current process from `self/stat` and `uptime` in the procfs mounted
+/// at `proc`, returning `None` if either file is missing or malformed.
+#[cfg(target_os = "linux")]
+fn process_age_in(proc: &std::path::Path) -> Option<Duration> {
+    // The 22nd field of `self/stat` is the start time in clock ticks after boot. The command
+    // name in the second field may contain spaces, so fields are counted after it.
+    let stat = std::fs::read_to_string(proc.join("self/stat")).ok()?;
+    let after_comm = &stat[stat.rfind(')')? + 1..];
+    let start_ticks: u64 = after_comm.split_whitespace().nth(19)?.parse().ok()?;
+    // SAFETY: `sysconf` has no preconditions.
+    let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_second <= 0 {
+        return None;
+    }
+    let started = Duration::from_secs_f64(start_ticks as f64 / ticks_per_second as f64);
+
+    let uptime = std::fs::read_to_string(proc.join("uptime")).ok()?;
+    let uptime: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    Duration::try_from_secs_f64(uptime)
+        .ok()?
+        .checked_sub(started)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_age() -> Option<Duration> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::format_with, Glog, GlogFields};
+
+    /// Formats `timer` on its own.
+    fn format(timer: &impl FormatTime) -> String {
+        let mut out = String::new();
+        timer.format_time(&mut Writer::new(&mut out)).unwrap();
+        out
+    }
+
+    /// Parses `+12.345678s` into a duration, checking the layout along the way.
+    fn parse_uptime(text: &str) -> Duration {
+        let secs = text
+            .strip_prefix('+')
+            .and_then(|text| text.strip_suffix('s'))
+            .unwrap_or_else(|| panic!("not an uptime: {:?}", text));
+        let (whole, fraction) = secs.split_once('.').unwrap();
+        assert_eq!(fraction.len(), 6, "{:?}", text);
+        Duration::new(
+            whole.parse().unwrap(),
+            fraction.parse::<u32>().unwrap() * 1_000,
+        )
+    }
+
+    #[test]
+    fn seconds_have_microsecond_precision() {
+        assert_eq!(Seconds(Duration::ZERO).to_string(), "0.000000");
+        assert_eq!(Seconds(Duration::from_micros(1)).to_string(), "0.000001");
+        assert_eq!(
+            Seconds(Duration::new(12, 345_678_999)).to_string(),
+            "12.345678"
+        );
+        assert_eq!(
+            Seconds(Duration::from_secs(86_400)).to_string(),
+            "86400.000000"
+        );
+    }
+
+    #[test]
+    fn uptime_counts_from_its_start() {
+        let elapsed = Duration::new(12, 345_678_000);
+        let before = Instant::now();
+        let Some(start) = before.checked_sub(elapsed) else {
+            // The monotonic clock started too recently to go back that far.
+            return;
+        };
+        let uptime = parse_uptime(&format(&Uptime::since(start)));
+        assert!(uptime >= elapsed, "{:?}", uptime);
+        assert!(
+            uptime <= elapsed + before.elapsed() + Duration::from_micros(1),
+            "{:?}",
+            uptime
+        );
+
+        assert!(parse_uptime(&format(&Uptime::default())) < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn bracketed_uptime_follows_the_prefix() {
+        let elapsed = Duration::new(12, 345_678_000);
+        let Some(start) = Instant::now().checked_sub(elapsed) else {
+            return;
+        };
+        let glog = Glog::default()
+            .with_timer(crate::UtcTime::default())
+            .with_bracketed_timer(Uptime::since(start));
+        let out = format_with(glog, GlogFields::default(), false, || {
+            tracing::info!("shaving yaks")
+        });
+
+        let line = out.lines().next().unwrap();
+        let (prefix, rest) = line.split_once("] ").unwrap();
+        assert!(prefix.starts_with('I'), "{:?}", line);
+        let (bracketed, message) = rest
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once("] "))
+            .unwrap_or_else(|| panic!("no bracketed timer: {:?}", line));
+        assert!(parse_uptime(bracketed) >= elapsed, "{:?}", line);
+        assert_eq!(message, "shaving yaks");
+    }
+
+    #[test]
+    fn monotonic_readings_increase() {
+        let first = format(&Monotonic::new());
+        let second = format(&Monotonic::new());
+        let parse = |text: &str| text.parse::<f64>().unwrap();
+        assert_eq!(first.split_once('.').unwrap().1.len(), 6, "{:?}", first);
+        assert!(parse(&first) <= parse(&second), "{} > {}", first, second);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn process_age_from_proc() {
+        use crate::testing::temp_dir;
+        use std::fs;
+
+        let proc = temp_dir("proc");
+        fs::create_dir(proc.join("self")).unwrap();
+        let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as u64;
+        // Started 10 seconds after boot, running for 2.5 seconds. The command name contains
+        // spaces and a parenthesis to check that fields are counted after the last one.
+        let stat = format!(
+            "1234 (yak ) shaver) S 1 1234 1234 0 -1 4194560 100 0 0 0 0 0 0 0 20 0 1 0 {} 0 0\n",
+            10 * ticks_per_second
+        );
+
+        // Both files missing.
+        assert_eq!(process_age_in(&proc), None);
+
+        fs::write(proc.join("self/stat"), &stat).unwrap();
+        // `uptime` missing.
+        assert_eq!(process_age_in(&proc), None);
+
+        fs::write(proc.join("uptime"), "12.50 40.00\n").unwrap();
+        assert_eq!(process_age_in(&proc), Some(Duration::from_millis(2_500)));
+
+        for uptime in [
+            "",
+            "twelve 40.00\n",
+            "-1.00 0.00\n",
+            "NaN 0.00\n",
+            "inf 0.00\n",
+        ] {
+            fs::write(proc.join("uptime"), uptime).unwrap();
+            assert_eq!(process_age_in(&proc), None, "uptime {:?}", uptime);
+        }
+        // An uptime before the process started.
+        fs::write(proc.join("uptime"), "9.00 40.00\n").unwrap();
+        assert_eq!(process_age_in(&proc), None);
+
+        fs::write(proc.join("uptime"), "12.50 40.00\n").unwrap();
+        for stat in [
+            "",
+            "1234 yak shaver S 1",
+            "1234 (yak) S 1 1234",
+            "1234 (yak) S 1 1234 1234 0 -1 4194560 100 0 0 0 0 0 0 0 20 0 1 0 soon 0",
+        ] {
+            fs::write(proc.join("self/stat"), stat).unwrap();
+            assert_eq!(process_age_in(&proc), None, "stat {:?}", stat);
+        }
+
+        fs::remove_dir_all(&proc).unwrap();
+    }
+
+    #[test]
+    fn since_process_start_is_in_the_past() {
+        let before = Instant::now();
+        let uptime = Uptime::since_process_start();
+        assert!(uptime.start <= before);
+        #[cfg(target_os = "linux")]
+        assert!(process_age().is_some());
+    }
+}
@@ -0,0 +1,409 @@
+use crate::{AnsiMode, Glog, GlogFields, LogFiles};
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+};
+use tracing::{level_filters::LevelFilter, Level, Metadata, Subscriber};
+use tracing_subscriber::{
+    fmt::{Layer as FmtLayer, MakeWriter},
+    registry::LookupSpan,
+};
+
+/// Writes every line to [`LogFiles`] and copies the severe ones to stderr, like glog's
+/// `--stderrthreshold`, `--alsologtostderr` and `--colorlogtostderr` flags.
+///
+/// Each event is formatted once, and the same line is written to both destinations. If the
+/// copy on stderr is colored, styling is removed from the line written to the files, so log
+/// files never contain ANSI escapes. Without log files (see [`GlogWriter::stderr`]), every
+/// line is written to stderr, as with glog's `--logtostderr`.
+///
+/// [`GlogWriter::layer`] builds a [`fmt::Layer`] that formats events with [`Glog`]. Whether
+/// a line is styled is decided once for the layer, not per event: when stderr is colored,
+/// every line is rendered with styling, including the lines that are only written to the
+/// files, and the styling is then removed from the copy written to the files.
+///
+/// # Examples
+///
+/// ```no_run
+/// use tracing_subscriber::prelude::*;
+/// use tracing_glog::{GlogWriter, LogFiles, ShutdownGuard};
+/// use tracing::level_filters::LevelFilter;
+///
+/// let _shutdown = ShutdownGuard::new();
+/// let layer = GlogWriter::new(LogFiles::new("/var/log/yaks"))
+///     .with_stderr_threshold(LevelFilter::WARN)
+///     .with_colorlogtostderr(true)
+///     .layer();
+/// tracing_subscriber::registry().with(layer).init();
+///
+/// tracing::info!("written to the INFO file only");
+/// tracing::warn!("written to the INFO and WARNING files, and in color to stderr");
+/// ```
+///
+/// [`fmt::Layer`]: tracing_subscriber::fmt::Layer
+#[derive(Debug)]
+pub struct GlogWriter<E = fn() -> io::Stderr> {
+    files: Option<LogFiles>,
+    stderr: E,
+    stderr_threshold: LevelFilter,
+    colorlogtostderr: bool,
+}
+
+/// The writer returned by [`GlogWriter`] for one event.
+#[derive(Debug)]
+pub struct GlogEventWriter<'a, E = fn() -> io::Stderr> {
+    writer: &'a GlogWriter<E>,
+    level: Level,
+}
+
+impl GlogWriter {
+    /// Returns a writer that writes every line to `files`, and copies lines of `ERROR`
+    /// severity to stderr.
+    pub fn new(files: LogFiles) -> Self {
+        GlogWriter {
+            files: Some(files),
+            stderr: io::stderr,
+            stderr_threshold: LevelFilter::ERROR,
+            colorlogtostderr: false,
+        }
+    }
+
+    /// Returns a writer that writes every line to stderr, like glog's `--logtostderr`.
+    pub fn stderr() -> Self {
+        GlogWriter {
+            files: None,
+            stderr: io::stderr,
+            stderr_threshold: LevelFilter::TRACE,
+            colorlogtostderr: false,
+        }
+    }
+}
+
+impl<E> GlogWriter<E>
+where
+    E: for<'w> MakeWriter<'w>,
+{
+    /// Sets the [`MakeWriter`] that receives the lines meant for stderr. Defaults to
+    /// [`io::stderr`].
+    ///
+    /// Whether lines are colored is still decided by whether stderr is a terminal (see
+    /// [`GlogWriter::with_colorlogtostderr`]).
+    pub fn with_stderr<E2>(self, stderr: E2) -> GlogWriter<E2>
+    where
+        E2: for<'w> MakeWriter<'w>,
+    {
+        GlogWriter {
+            files: self.files,
+            stderr,
+            stderr_threshold: self.stderr_threshold,
+            colorlogtostderr: self.colorlogtostderr,
+        }
+    }
+
+    /// Sets the least severe level that is copied to stderr when writing to log files, like
+    /// glog's `--stderrthreshold`. Defaults to `ERROR`.
+    ///
+    /// `LevelFilter::TRACE` copies every line, like `--alsologtostderr`, and
+    /// `LevelFilter::OFF` copies none. Without log files, every line is written to stderr
+    /// regardless of the threshold.
+    pub fn with_stderr_threshold(self, stderr_threshold: LevelFilter) -> Self {
+        GlogWriter {
+            stderr_threshold,
+            ..self
+        }
+    }
+
+    /// Sets whether the lines written to stderr are colored, like glog's
+    /// `--colorlogtostderr`. Defaults to false.
+    ///
+    /// As in glog, lines are only colored if stderr is a terminal. The environment variables
+    /// described in [`AnsiMode::Auto`] override this, so that `NO_COLOR` disables colors and
+    /// `CLICOLOR_FORCE=1` enables them when stderr is redirected. Lines written to log files
+    /// are never colored.
+    pub fn with_colorlogtostderr(self, colorlogtostderr: bool) -> Self {
+        GlogWriter {
+            colorlogtostderr: colorlogtostderr && AnsiMode::Auto.is_enabled_for(&io::stderr()),
+            ..self
+        }
+    }
+
+    /// Returns a [`fmt::Layer`] that formats events with [`Glog`] and [`GlogFields`] and
+    /// writes them with this writer.
+    ///
+    /// The layer renders styling for every event if stderr is colored, and for none
+    /// otherwise. To customize the formatting,
+    /// replace the formatters with [`fmt::Layer::event_format`] and
+    /// [`fmt::Layer::fmt_fields`].
+    ///
+    /// [`fmt::Layer`]: tracing_subscriber::fmt::Layer
+    /// [`fmt::Layer::event_format`]: tracing_subscriber::fmt::Layer::event_format
+    /// [`fmt::Layer::fmt_fields`]: tracing_subscriber::fmt::Layer::fmt_fields
+    pub fn layer<S>(self) -> FmtLayer<S, GlogFields, Glog, GlogWriter<E>>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+        E: 'static,
+    {
+        FmtLayer::default()
+            .with_ansi(self.colorlogtostderr)
+            .event_format(Glog::default())
+            .fmt_fields(GlogFields::default())
+            .with_writer(self)
+    }
+
+    fn to_stderr(&self, level: Level) -> bool {
+        self.files.is_none() || self.stderr_threshold >= level
+    }
+
+    fn write_line(&self, level: Level, line: &[u8]) -> io::Result<()> {
+        let styled = line.contains(&ESC);
+        let mut result = Ok(());
+        if let Some(files) = &self.files {
+            let plain: Cow<'_, [u8]> = if styled {
+                strip_ansi(line).into()
+            } else {
+                line.into()
+            };
+            result = files.writer_for(level).write_all(&plain);
+        }
+        if self.to_stderr(level) {
+            let line: Cow<'_, [u8]> = if styled && !self.colorlogtostderr {
+                strip_ansi(line).into()
+            } else {
+                line.into()
+            };
+            result = result.and(self.stderr.make_writer().write_all(&line));
+        }
+        result
+    }
+}
+
+impl<'a, E> MakeWriter<'a> for GlogWriter<E>
+where
+    E: for<'w> MakeWriter<'w> + 'a,
+{
+    type Writer = GlogEventWriter<'a, E>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        GlogEventWriter {
+            writer: self,
+            level: Level::INFO,
+        }
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        GlogEventWriter {
+            writer: self,
+            level: *meta.level(),
+        }
+    }
+}
+
+impl<'a, E> Write for GlogEventWriter<'a, E>
+where
+    E: for<'w> MakeWriter<'w>,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write_line(self.level, buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(files) = &self.writer.files {
+            files.writer_for(self.level).flush()?;
+        }
+        self.writer.stderr.make_writer().flush()
+    }
+}
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+/// Removes ANSI escape sequences, such as the SGR sequences that select colors and OSC 8
+/// hyperlinks, from `line`.
+fn strip_ansi(line: &[u8]) -> Vec<u8> {
+    let mut plain = Vec::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.iter().position(|&b| b == ESC) {
+        plain.extend_from_slice(&rest[..start]);
+        rest = &rest[start + 1..];
+        match rest.first() {
+            Some(b'[') => {
+                // A control sequence ends with a byte in `@`..=`~`.
+                let end = rest[1..]
+                    .iter()
+                    .position(|b| (b'@'..=b'~').contains(b))
+                    .map_or(rest.len(), |end| end + 2);
+                rest = &rest[end..];
+            }
+            Some(b']') => {
+                // An operating system command, such as a hyperlink, ends with BEL or `ESC \`.
+                let end =
+                    rest.iter()
+                        .position(|&b| b == BEL || b == ESC)
+                        .map_or(rest.len(), |end| match rest[end] {
+                            ESC if rest.get(end + 1) == Some(&b'\\') => end + 2,
+                            ESC => end,
+                            _ => end + 1,
+                        });
+                rest = &rest[end..];
+            }
+            _ => {}
+        }
+    }
+    plain.extend_from_slice(rest);
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{lines_in, temp_dir, Capture};
+    use std::{fs, path::Path};
+    use tracing_subscriber::prelude::*;
+
+    /// Logs one event of each level through `writer`'s layer, and returns the message of
+    /// each line written to stderr.
+    fn log_each_level<E>(writer: GlogWriter<E>, stderr: &Capture) -> Vec<String>
+    where
+        E: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+    {
+        let subscriber = tracing_subscriber::registry().with(writer.layer());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::trace!("trace");
+            tracing::debug!("debug");
+            tracing::info!("info");
+            tracing::warn!("warn");
+            tracing::error!("error");
+        });
+        messages(&stderr.contents())
+    }
+
+    /// Returns the message of each line in `lines`.
+    fn messages(lines: &str) -> Vec<String> {
+        lines
+            .lines()
+            .map(|line| line.rsplit_once("] ").unwrap().1.to_string())
+            .collect()
+    }
+
+    /// Returns log files in a new directory that write every line immediately.
+    fn log_files(dir: &Path) -> LogFiles {
+        LogFiles::new(dir)
+            .with_program_name("yaks")
+            .with_flush_level(Level::TRACE)
+    }
+
+    #[test]
+    fn lines_below_the_threshold_only_reach_the_files() {
+        let dir = temp_dir("stderr-threshold");
+        let stderr = Capture::default();
+        let writer = GlogWriter::new(log_files(&dir))
+            .with_stderr_threshold(LevelFilter::WARN)
+            .with_stderr(stderr.clone());
+        assert_eq!(log_each_level(writer, &stderr), ["warn", "error"]);
+        assert_eq!(
+            messages(&lines_in(&dir, "INFO")),
+            ["trace", "debug", "info", "warn", "error"]
+        );
+        assert_eq!(messages(&lines_in(&dir, "WARNING")), ["warn", "error"]);
+        assert_eq!(messages(&lines_in(&dir, "ERROR")), ["error"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn thresholds_off_and_trace() {
+        let dir = temp_dir("stderr-threshold-off");
+        let stderr = Capture::default();
+        let writer = GlogWriter::new(log_files(&dir))
+            .with_stderr_threshold(LevelFilter::OFF)
+            .with_stderr(stderr.clone());
+        assert!(log_each_level(writer, &stderr).is_empty());
+        assert_eq!(messages(&lines_in(&dir, "INFO")).len(), 5);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let dir = temp_dir("stderr-threshold-trace");
+        let stderr = Capture::default();
+        let writer = GlogWriter::new(log_files(&dir))
+            .with_stderr_threshold(LevelFilter::TRACE)
+            .with_stderr(stderr.clone());
+        assert_eq!(
+            log_each_level(writer, &stderr),
+            ["trace", "debug", "info", "warn", "error"]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Without log files, the threshold is ignored.
+        let stderr = Capture::default();
+        let writer = GlogWriter::stderr()
+            .with_stderr_threshold(LevelFilter::OFF)
+            .with_stderr(stderr.clone());
+        assert_eq!(
+            log_each_level(writer, &stderr),
+            ["trace", "debug", "info", "warn", "error"]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "ansi")]
+    fn files_are_never_colored() {
+        let dir = temp_dir("colorlogtostderr");
+        let stderr = Capture::default();
+        let writer = GlogWriter {
+            // Stderr is not a terminal under test, so `with_colorlogtostderr` would not
+            // enable colors.
+            colorlogtostderr: true,
+            ..GlogWriter::new(log_files(&dir)).with_stderr(stderr.clone())
+        };
+        log_each_level(writer, &stderr);
+        assert!(stderr.bytes().contains(&ESC));
+        for severity in ["INFO", "WARNING", "ERROR"] {
+            let lines = lines_in(&dir, severity);
+            assert!(!lines.as_bytes().contains(&ESC), "{:?}", lines);
+        }
+        assert_eq!(
+            messages(&lines_in(&dir, "INFO")),
+            ["trace", "debug", "info", "warn", "error"]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn strip_sgr() {
+        assert_eq!(strip_ansi(b"no escapes\n"), b"no escapes\n");
+        assert_eq!(
+            strip_ansi(b"\x1b[32mI\x1b[0m\x1b[2m1201 01:13:04\x1b[0m \x1b[1mmain\x1b[0m\n"),
+            b"I1201 01:13:04 main\n"
+        );
+        assert_eq!(strip_ansi(b"\x1b[38;5;208mW\x1b[m"), b"W");
+    }
+
+    #[test]
+    fn strip_osc_8_hyperlinks() {
+        // Ended by BEL.
+        assert_eq!(
+            strip_ansi(b"\x1b]8;;file:///src/lib.rs\x07lib.rs:10\x1b]8;;\x07]"),
+            b"lib.rs:10]"
+        );
+        // Ended by the string terminator, `ESC \`.
+        assert_eq!(
+            strip_ansi(b"\x1b]8;;file:///src/lib.rs\x1b\\lib.rs:10\x1b]8;;\x1b\\]"),
+            b"lib.rs:10]"
+        );
+        // Styled text within a hyperlink.
+        assert_eq!(
+            strip_ansi(b"\x1b]8;;file:///a.rs\x1b\\\x1b[1ma.rs\x1b[0m\x1b]8;;\x1b\\"),
+            b"a.rs"
+        );
+    }
+
+    #[test]
+    fn strip_truncated_sequences() {
+        assert_eq!(strip_ansi(b"line\x1b"), b"line");
+        assert_eq!(strip_ansi(b"line\x1b[1;3"), b"line");
+        assert_eq!(strip_ansi(b"line\x1b]8;;file:///a.rs"), b"line");
+        // An OSC that is interrupted by another escape sequence ends there.
+        assert_eq!(strip_ansi(b"\x1b]8;;file:///a.rs\x1b[1ma.rs"), b"a.rs");
+        // An escape that does not start a sequence is dropped on its own.
+        assert_eq!(strip_ansi(b"a\x1bb"), b"ab");
+    }
+}
@@ -0,0 +1,98 @@
+//! Checks that formatting events whose fields are all primitives does not allocate once the
+//! thread-local buffers are warm.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    io,
+};
+use tracing::{info, Dispatch};
+use tracing_glog::{CachedUtcTime, Glog, GlogFields};
+use tracing_subscriber::fmt::time::FormatTime;
+
+/// Counts heap allocations made by the current thread, so that tests running in parallel do
+/// not disturb each other's counts.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count_allocation() {
+    // The counter may already be gone while the thread is being torn down.
+    let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+}
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn dispatch<T: FormatTime + Send + Sync + 'static>(format: Glog<T>) -> Dispatch {
+    Dispatch::new(
+        tracing_subscriber::fmt()
+            .with_writer(io::sink)
+            .event_format(format)
+            .fmt_fields(GlogFields::default())
+            .finish(),
+    )
+}
+
+fn event() {
+    info!(yaks = 3, shaved = true, ratio = 0.5, "shaving yaks");
+}
+
+/// Panics if formatting `f`'s events with `dispatch` allocates once the buffers are warm.
+fn assert_no_allocations(name: &str, dispatch: &Dispatch, f: fn()) {
+    tracing::dispatcher::with_default(dispatch, || {
+        for _ in 0..16 {
+            f();
+        }
+        let before = allocations();
+        for _ in 0..1024 {
+            f();
+        }
+        let allocations = allocations() - before;
+        assert_eq!(
+            allocations, 0,
+            "{} allocated {} times while formatting 1024 events",
+            name, allocations
+        );
+    });
+}
+
+#[test]
+fn allocations_are_counted() {
+    let before = allocations();
+    drop(std::hint::black_box(Box::new(1)));
+    assert_eq!(allocations() - before, 1);
+}
+
+#[test]
+fn glog_does_not_allocate() {
+    assert_no_allocations("Glog", &dispatch(Glog::default()), event);
+}
+
+#[test]
+fn glog_with_cached_time_does_not_allocate() {
+    let format = Glog::default().with_timer(CachedUtcTime::new());
+    assert_no_allocations("Glog+CachedUtcTime", &dispatch(format), event);
+}